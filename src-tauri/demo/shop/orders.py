@@ -0,0 +1,23 @@
+"""A tiny order pipeline for the bundled demo repo."""
+
+TAX_RATE = 0.08
+
+
+def subtotal(items):
+    total = 0
+    for item in items:
+        total += item["price"] * item["qty"]
+    return total
+
+
+def apply_discount(amount, code):
+    if code == "SAVE10":
+        return amount * 0.9
+    return amount
+
+
+def checkout(items, code=None):
+    amount = subtotal(items)
+    amount = apply_discount(amount, code)
+    tax = round(amount * TAX_RATE, 2)
+    return {"amount": amount, "tax": tax, "total": amount + tax}
@@ -0,0 +1,684 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::error::FlowlensError;
+use crate::payload::PayloadLimits;
+use crate::process::output_logged;
+use crate::tools::TOOLS;
+
+// ------------------------
+// Repo Configuration
+// ------------------------
+
+/// Repo used until the user configures one with `set_repo_path`.
+pub const DEFAULT_REPO: &str = "/home/bimal/Documents/ucsd/research/code/trap";
+
+#[derive(Default, Clone)]
+pub struct AppConfig {
+    pub repo_path: Option<PathBuf>,
+    /// `repo_path` is the bundled demo, which has no git history
+    pub demo: bool,
+    /// The repo `repo_path` is a temporary worktree of, for `at_commit` traces
+    pub checkout_of: Option<PathBuf>,
+    /// Overrides `FLOWLENS_TOOLS_DIR` and the default tools location
+    pub tools_dir: Option<PathBuf>,
+    /// Tools shipped in the app's resource dir, found at startup. Unset in
+    /// dev builds, which run them from the source tree.
+    pub bundled_tools_dir: Option<PathBuf>,
+    /// Where the tracer writes its log files, passed as `FLOWLENS_LOG_DIR`;
+    /// next to the tools when unset
+    pub tracer_log_dir: Option<PathBuf>,
+    /// Start Python with only `ISOLATED_ENV_KEEP` from the app's environment
+    pub isolate_env: bool,
+    /// Variables set for every Python process, isolated or not
+    pub env_overrides: HashMap<String, String>,
+    /// Extra spawn attempts when the tracer exits before its first event;
+    /// `DEFAULT_SPAWN_RETRIES` when unset
+    pub spawn_retries: Option<u32>,
+    /// Newest stray output lines each tracer keeps; `DEFAULT_TRACER_LOG_LINES` when unset
+    pub tracer_log_lines: Option<usize>,
+    /// Seconds to wait for the tracer's next event line; `DEFAULT_READ_TIMEOUT_SECS` when unset
+    pub read_timeout_secs: Option<u64>,
+    /// Tracers kept running at once before the least recently used unpinned
+    /// one is stopped; `DEFAULT_MAX_TRACERS` when unset
+    pub max_tracers: Option<usize>,
+    /// Source lines either side of the paused line attached to each event as
+    /// `context`, 0 for none; `DEFAULT_CONTEXT_LINES` when unset
+    pub context_lines: Option<usize>,
+    /// Interpreter resolved for `repo_path` at startup or the last repo change
+    pub python: Option<PythonInterpreter>,
+    /// Interpreter for every repo, ahead of `PYTHON_BIN` and venvs; from `FLOWLENS_PYTHON`
+    pub python_bin: Option<PathBuf>,
+    pub events_on: EventChannel,
+    pub payload_limits: PayloadLimits,
+    pub default_stop: DefaultStop,
+    /// Repo-relative directories Python puts first on `sys.path`, e.g. `src`
+    pub sys_path: Vec<String>,
+    /// Tracer backend for requests that don't pick one; the interpreter's default when unset
+    pub tracer_backend: Option<String>,
+}
+
+/// Which of the tracer's output streams carries JSON events. The other one is
+/// left for diagnostics. Stdout by default, so Python's warnings and
+/// tracebacks on stderr never mix with events.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum EventChannel {
+    #[default]
+    Stdout,
+    Stderr,
+}
+
+/// Where a new trace first pauses when its request has neither a `stop_line`
+/// nor breakpoints.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DefaultStop {
+    /// The first line the entry function executes
+    #[default]
+    FirstLine,
+    /// The entry function's call event, before any of its lines run
+    EntryOnly,
+    /// Nowhere: run to the end and report the return
+    NoStop,
+}
+
+/// How the tracer follows execution. Only `sys.settrace` exists today; new
+/// backends (e.g. `sys.monitoring` on 3.12+) are added here with the oldest
+/// Python they run on, and to `--backend` in the tracer.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TracerBackend {
+    /// `bdb`, driven by `sys.settrace`
+    Settrace,
+}
+
+impl TracerBackend {
+    /// Every backend, least preferred first.
+    pub const ALL: &'static [TracerBackend] = &[TracerBackend::Settrace];
+
+    pub fn as_arg(self) -> &'static str {
+        match self {
+            TracerBackend::Settrace => "settrace",
+        }
+    }
+
+    /// Oldest Python (major, minor) the backend runs on.
+    pub fn min_python(self) -> (u32, u32) {
+        match self {
+            TracerBackend::Settrace => (3, 8),
+        }
+    }
+
+    /// Backends `version` supports, least preferred first. All of them when
+    /// the version is unknown.
+    pub fn available(version: Option<(u32, u32)>) -> Vec<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .filter(|backend| version.is_none_or(|version| version >= backend.min_python()))
+            .collect()
+    }
+
+    /// The most preferred backend `version` supports.
+    pub fn default_for(version: Option<(u32, u32)>) -> Self {
+        Self::available(version).pop().unwrap_or(TracerBackend::Settrace)
+    }
+
+    /// `name` if it is a backend `version` supports.
+    pub fn parse(name: &str, version: Option<(u32, u32)>) -> Result<Self, FlowlensError> {
+        let available = Self::available(version);
+        available
+            .iter()
+            .copied()
+            .find(|backend| backend.as_arg() == name)
+            .ok_or_else(|| FlowlensError::InvalidRequest {
+                message: format!(
+                    "unknown tracer backend {:?} for Python {}; available: {}",
+                    name,
+                    version.map_or("(unknown version)".to_string(), |(major, minor)| format!("{}.{}", major, minor)),
+                    available.iter().map(|backend| backend.as_arg()).collect::<Vec<_>>().join(", ")
+                ),
+            })
+    }
+}
+
+impl EventChannel {
+    pub fn as_arg(self) -> &'static str {
+        match self {
+            EventChannel::Stdout => "stdout",
+            EventChannel::Stderr => "stderr",
+        }
+    }
+}
+
+/// Retries after a tracer dies on spawn, e.g. from a transient import error.
+pub const DEFAULT_SPAWN_RETRIES: u32 = 2;
+
+pub const DEFAULT_TRACER_LOG_LINES: usize = 1000;
+
+/// Generous enough for a step over a slow call; a hung tracer fails after this.
+pub const DEFAULT_READ_TIMEOUT_SECS: u64 = 300;
+
+/// Shorter read timeouts are rejected, so a busy machine doesn't fail every step.
+pub const MIN_READ_TIMEOUT_SECS: u64 = 1;
+
+/// Enough flows to compare side by side; each one is a Python process.
+pub const DEFAULT_MAX_TRACERS: usize = 8;
+
+/// Enough to show a step in place without fetching the source.
+pub const DEFAULT_CONTEXT_LINES: usize = 3;
+
+/// Larger windows are rejected; past this, fetch the source once instead.
+pub const MAX_CONTEXT_LINES: usize = 20;
+
+impl AppConfig {
+    pub fn spawn_retries(&self) -> u32 {
+        self.spawn_retries.unwrap_or(DEFAULT_SPAWN_RETRIES)
+    }
+
+    pub fn tracer_log_lines(&self) -> usize {
+        self.tracer_log_lines.unwrap_or(DEFAULT_TRACER_LOG_LINES)
+    }
+
+    pub fn read_timeout(&self) -> Duration {
+        Duration::from_secs(self.read_timeout_secs.unwrap_or(DEFAULT_READ_TIMEOUT_SECS))
+    }
+
+    pub fn max_tracers(&self) -> usize {
+        self.max_tracers.unwrap_or(DEFAULT_MAX_TRACERS)
+    }
+
+    pub fn context_lines(&self) -> usize {
+        self.context_lines.unwrap_or(DEFAULT_CONTEXT_LINES)
+    }
+
+    /// Absolute path of the repo every command operates on.
+    pub fn repo(&self) -> PathBuf {
+        self.repo_path
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_REPO))
+    }
+}
+
+pub type SharedConfig = Mutex<AppConfig>;
+
+/// Env var pointing at a working copy of the Python tools, so they can be
+/// edited without rebuilding the app.
+pub const TOOLS_DIR_ENV: &str = "FLOWLENS_TOOLS_DIR";
+
+/// Tools location in dev builds: `tools` in the source tree, wherever the
+/// app is started from. Release builds never look there, since the path only
+/// exists on the machine that built them.
+#[cfg(debug_assertions)]
+const DEV_TOOLS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../tools");
+
+/// Env var telling the tracer where to write its log files.
+pub const TRACER_LOG_DIR_ENV: &str = "FLOWLENS_LOG_DIR";
+
+impl AppConfig {
+    /// Directory holding the Python tools: the `tools_dir` setting, then
+    /// `FLOWLENS_TOOLS_DIR`, then the bundled tools, then (dev builds only)
+    /// the source tree.
+    pub fn tools_dir(&self) -> PathBuf {
+        if let Some(dir) = &self.tools_dir {
+            return dir.clone();
+        }
+        match std::env::var(TOOLS_DIR_ENV) {
+            Ok(dir) if !dir.trim().is_empty() => PathBuf::from(dir.trim()),
+            #[cfg(debug_assertions)]
+            _ => self.bundled_tools_dir.clone().unwrap_or_else(|| PathBuf::from(DEV_TOOLS_DIR)),
+            // Scripts missing from the bundle then fail with `ScriptNotFound` naming it
+            #[cfg(not(debug_assertions))]
+            _ => self.bundled_tools_dir.clone().unwrap_or_default(),
+        }
+    }
+
+    pub fn script_path(&self, script: &str) -> PathBuf {
+        self.tools_dir().join(script)
+    }
+
+    /// `script_path`, failing with `ScriptNotFound` when the script isn't
+    /// there, before Python is started and reports it less clearly.
+    pub fn resolve_script(&self, script: &str) -> Result<PathBuf, FlowlensError> {
+        let path = self.script_path(script);
+        if path.is_file() {
+            return Ok(path);
+        }
+        Err(FlowlensError::ScriptNotFound {
+            script: script.to_string(),
+            tools_dir: self.tools_dir().display().to_string(),
+        })
+    }
+}
+
+/// Scripts from `TOOLS` that are not files in `dir`.
+pub fn missing_tools(dir: &Path) -> Vec<&'static str> {
+    TOOLS
+        .iter()
+        .map(|tool| tool.script)
+        .filter(|script| !dir.join(script).is_file())
+        .collect()
+}
+
+/// Variables passed through to Python when `isolate_env` is on: the executable
+/// search path, home/locale, the active virtualenv, and Python's own path settings.
+pub const ISOLATED_ENV_KEEP: &[&str] = &[
+    "PATH",
+    "HOME",
+    "LANG",
+    "LC_ALL",
+    "TMPDIR",
+    "VIRTUAL_ENV",
+    "PYTHONHOME",
+    "PYTHONPATH",
+    "SYSTEMROOT",
+];
+
+impl AppConfig {
+    /// A `Command` for a Python process, with the environment this config asks for.
+    pub fn python_command(&self, program: impl AsRef<std::ffi::OsStr>) -> Command {
+        let mut cmd = Command::new(program);
+        if self.isolate_env {
+            cmd.env_clear();
+            for key in ISOLATED_ENV_KEEP {
+                if let Some(value) = std::env::var_os(key) {
+                    cmd.env(key, value);
+                }
+            }
+            cmd.env("PYTHONUNBUFFERED", "1");
+        }
+        if let Some(dir) = &self.tracer_log_dir {
+            cmd.env(TRACER_LOG_DIR_ENV, dir);
+        }
+        cmd.envs(&self.env_overrides);
+        cmd
+    }
+}
+
+impl AppConfig {
+    /// The cached interpreter, resolving it on the spot if nothing is cached yet.
+    pub fn python(&self) -> PythonInterpreter {
+        self.python.clone().unwrap_or_else(|| resolve_python(&self.repo(), self.python_bin.as_deref()))
+    }
+
+    /// Backend for a request asking for `requested`, else the configured one,
+    /// else the interpreter's default.
+    pub fn tracer_backend(&self, requested: Option<&str>) -> Result<TracerBackend, FlowlensError> {
+        let version = self.python().version;
+        match requested.or(self.tracer_backend.as_deref()) {
+            Some(name) => TracerBackend::parse(name, version),
+            None => Ok(TracerBackend::default_for(version)),
+        }
+    }
+
+    /// `python`, failing when it can't be run or is older than `MIN_PYTHON`.
+    /// Uses the cached interpreter, so this doesn't start Python.
+    pub fn checked_python(&self) -> Result<PythonInterpreter, FlowlensError> {
+        let python = self.python();
+        match python.version {
+            None => Err(FlowlensError::PythonNotFound {
+                python: python.path.display().to_string(),
+            }),
+            Some(version) if version < MIN_PYTHON => Err(FlowlensError::PythonTooOld {
+                python: python.path.display().to_string(),
+                version: python.release.clone().unwrap_or_default(),
+                minimum: format!("{}.{}", MIN_PYTHON.0, MIN_PYTHON.1),
+            }),
+            Some(_) => Ok(python),
+        }
+    }
+
+    /// Re-run interpreter resolution for the current repo, e.g. after a venv was created.
+    pub fn refresh_python(&mut self) -> &PythonInterpreter {
+        let python = resolve_python(&self.repo(), self.python_bin.as_deref());
+        println!("[flowlens] python for {}: {}", self.repo().display(), python.path.display());
+        self.python.insert(python)
+    }
+}
+
+/// Interpreter used to run the tracer against a repo.
+#[derive(Serialize, Clone, Debug)]
+pub struct PythonInterpreter {
+    pub path: PathBuf,
+    /// True when no `PYTHON_BIN` or repo venv was found and bare `python3` is used
+    pub fallback: bool,
+    /// (major, minor), or `None` when the interpreter couldn't be run
+    pub version: Option<(u32, u32)>,
+    /// Full version as `--version` reports it, e.g. `3.11.4`
+    pub release: Option<String>,
+}
+
+/// Oldest Python the tools run on.
+pub const MIN_PYTHON: (u32, u32) = (3, 8);
+
+/// Pick the interpreter for `repo`: `python_bin` if given, then `PYTHON_BIN`,
+/// then the repo's `.venv` or `venv`, and finally whatever `python3` is on
+/// `PATH`. This probes the filesystem, so use `AppConfig::python` rather than
+/// calling it per spawn.
+pub fn resolve_python(repo: &Path, python_bin: Option<&Path>) -> PythonInterpreter {
+    let from_env = std::env::var("PYTHON_BIN").ok().map(PathBuf::from);
+    let (path, fallback) = match python_bin.map(Path::to_path_buf).or(from_env) {
+        Some(bin) => (bin, false),
+        None => {
+            let venv_python = [".venv", "venv"]
+                .iter()
+                .map(|dir| repo.join(dir).join("bin").join("python"))
+                .find(|path| path.is_file());
+            match venv_python {
+                Some(path) => (path, false),
+                None => (find_on_path("python3").unwrap_or_else(|| PathBuf::from("python3")), true),
+            }
+        }
+    };
+    let release = python_release(&path);
+    let version = release.as_deref().and_then(major_minor);
+    PythonInterpreter { path, fallback, version, release }
+}
+
+/// Version reported by `<path> --version`, e.g. `3.11.4`. Python 2 prints it
+/// on stderr, so both streams are read.
+fn python_release(path: &Path) -> Option<String> {
+    let output = output_logged(Command::new(path).arg("--version")).ok()?;
+    let text = [output.stdout, output.stderr].concat();
+    let text = String::from_utf8_lossy(&text);
+    let release = text.trim().strip_prefix("Python ")?;
+    Some(release.split_whitespace().next()?.to_string())
+}
+
+/// (major, minor) of a release like `3.11.4` or `3.13.0rc1`.
+fn major_minor(release: &str) -> Option<(u32, u32)> {
+    let mut parts = release.split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor: String = parts.next()?.chars().take_while(char::is_ascii_digit).collect();
+    Some((major, minor.parse().ok()?))
+}
+
+fn find_on_path(program: &str) -> Option<PathBuf> {
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(program))
+        .find(|path| path.is_file())
+}
+
+/// Check that every entry of `dirs` is a directory inside `repo`, and return
+/// them relative to it.
+pub fn resolve_sys_path(repo: &Path, dirs: &[String]) -> Result<Vec<String>, FlowlensError> {
+    let invalid = |message: String| FlowlensError::InvalidRequest { message };
+    let root = repo
+        .canonicalize()
+        .map_err(|e| invalid(format!("cannot resolve repo {}: {}", repo.display(), e)))?;
+    dirs.iter()
+        .map(|dir| {
+            let dir = dir.trim();
+            if dir.is_empty() || Path::new(dir).is_absolute() {
+                return Err(invalid(format!("sys_path entry {:?} must be a repo-relative directory", dir)));
+            }
+            let path = root
+                .join(dir)
+                .canonicalize()
+                .map_err(|e| invalid(format!("sys_path entry {} not found: {}", dir, e)))?;
+            match path.strip_prefix(&root) {
+                Ok(rel) if path.is_dir() => Ok(rel.to_string_lossy().into_owned()),
+                Ok(_) => Err(invalid(format!("sys_path entry {} is not a directory", dir))),
+                Err(_) => Err(invalid(format!("sys_path entry {} is outside the repo", dir))),
+            }
+        })
+        .collect()
+}
+
+/// Turn a user-typed repo path into an absolute directory path.
+///
+/// `~` and `~/...` expand to the home directory. Relative paths are resolved
+/// against the home directory too, since the app's working directory is
+/// arbitrary (it is `src-tauri` in dev and `/` for packaged builds).
+pub fn resolve_repo_path(raw: &str) -> Result<PathBuf, FlowlensError> {
+    resolve_repo_path_in(raw, dirs::home_dir(), &std::env::var(ALLOWED_ROOTS_ENV).unwrap_or_default())
+}
+
+/// `resolve_repo_path` with the home directory and allowed roots passed in.
+fn resolve_repo_path_in(raw: &str, home: Option<PathBuf>, allowed_roots: &str) -> Result<PathBuf, FlowlensError> {
+    let raw = raw.trim();
+    let invalid = |reason: String| FlowlensError::InvalidRepoPath {
+        path: raw.to_string(),
+        reason,
+    };
+    if raw.is_empty() {
+        return Err(invalid("repo path is empty".to_string()));
+    }
+
+    let home = || home.clone().ok_or_else(|| invalid("could not determine home directory".to_string()));
+
+    let expanded = if raw == "~" {
+        home()?
+    } else if let Some(rest) = raw.strip_prefix("~/") {
+        home()?.join(rest)
+    } else if Path::new(raw).is_relative() {
+        home()?.join(raw)
+    } else {
+        PathBuf::from(raw)
+    };
+
+    let absolute = expanded
+        .canonicalize()
+        .map_err(|e| invalid(format!("{} does not exist: {}", expanded.display(), e)))?;
+
+    if !absolute.is_dir() {
+        return Err(invalid(format!("{} is not a directory", absolute.display())));
+    }
+
+    check_roots(&absolute, allowed_roots)?;
+
+    Ok(absolute)
+}
+
+/// Repo to open at startup, resolved like `set_repo_path` input.
+pub const REPO_ENV: &str = "FLOWLENS_REPO";
+
+/// Interpreter to use for every repo; see `AppConfig::python_bin`.
+pub const PYTHON_ENV: &str = "FLOWLENS_PYTHON";
+
+/// Seconds to wait for each tracer event; see `AppConfig::read_timeout_secs`.
+pub const TIMEOUT_ENV: &str = "FLOWLENS_TIMEOUT";
+
+impl AppConfig {
+    /// Defaults seeded from `FLOWLENS_REPO`, `FLOWLENS_PYTHON` and
+    /// `FLOWLENS_TIMEOUT`, so a headless setup needs no UI. `FLOWLENS_TOOLS_DIR`
+    /// and `FLOWLENS_ALLOWED_ROOTS` are read where they are used. Invalid values
+    /// are logged and skipped rather than failing startup; runtime commands
+    /// override all of them.
+    pub fn from_env() -> Self {
+        let mut config = AppConfig::default();
+        let var = |name: &str| std::env::var(name).ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+
+        if let Some(raw) = var(REPO_ENV) {
+            // Subject to FLOWLENS_ALLOWED_ROOTS like any other repo path
+            match resolve_repo_path(&raw) {
+                Ok(repo) => config.repo_path = Some(repo),
+                Err(e) => println!("[flowlens] ignoring {}: {}", REPO_ENV, e),
+            }
+        }
+        if let Some(raw) = var(PYTHON_ENV) {
+            config.python_bin = Some(PathBuf::from(raw));
+        }
+        if let Some(raw) = var(TIMEOUT_ENV) {
+            match raw.parse::<u64>() {
+                Ok(secs) if secs >= MIN_READ_TIMEOUT_SECS => config.read_timeout_secs = Some(secs),
+                _ => println!(
+                    "[flowlens] ignoring {}={:?}: expected whole seconds, at least {}",
+                    TIMEOUT_ENV, raw, MIN_READ_TIMEOUT_SECS
+                ),
+            }
+        }
+        println!(
+            "[flowlens] config from env: repo={:?} python={:?} read_timeout_secs={:?}",
+            config.repo_path, config.python_bin, config.read_timeout_secs
+        );
+        config
+    }
+}
+
+/// Env var holding a colon-separated list of directories repos must live under.
+pub const ALLOWED_ROOTS_ENV: &str = "FLOWLENS_ALLOWED_ROOTS";
+
+/// Reject `repo` unless it sits under one of the roots in `FLOWLENS_ALLOWED_ROOTS`.
+/// When the variable is unset or empty every directory is allowed.
+pub fn check_allowed_root(repo: &Path) -> Result<(), FlowlensError> {
+    check_roots(repo, &std::env::var(ALLOWED_ROOTS_ENV).unwrap_or_default())
+}
+
+/// `check_allowed_root` against `raw`, a colon-separated list of roots.
+fn check_roots(repo: &Path, raw: &str) -> Result<(), FlowlensError> {
+    let entries: Vec<&str> = raw.split(':').map(str::trim).filter(|r| !r.is_empty()).collect();
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    // Roots that don't exist can't contain anything, so they are dropped
+    let roots: Vec<PathBuf> = entries
+        .iter()
+        .filter_map(|r| Path::new(r).canonicalize().ok())
+        .collect();
+
+    if roots.iter().any(|root| repo.starts_with(root)) {
+        return Ok(());
+    }
+
+    Err(FlowlensError::PathNotAllowed {
+        path: repo.display().to_string(),
+        allowed_roots: roots.iter().map(|r| r.display().to_string()).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A scratch directory, deleted when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("flowlens-config-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir.canonicalize().unwrap())
+        }
+
+        fn mkdir(&self, rel: &str) -> PathBuf {
+            let dir = self.0.join(rel);
+            std::fs::create_dir_all(&dir).unwrap();
+            dir
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn not_allowed(result: Result<PathBuf, FlowlensError>) -> bool {
+        matches!(result, Err(FlowlensError::PathNotAllowed { .. }))
+    }
+
+    #[test]
+    fn check_roots_allows_everything_without_roots() {
+        assert!(check_roots(Path::new("/anywhere"), "").is_ok());
+        assert!(check_roots(Path::new("/anywhere"), " : :").is_ok());
+    }
+
+    #[test]
+    fn check_roots_matches_whole_components() {
+        let tmp = TempDir::new("prefix");
+        let root = tmp.mkdir("repo");
+        let sibling = tmp.mkdir("repo2");
+        let allowed = root.display().to_string();
+
+        assert!(check_roots(&root, &allowed).is_ok());
+        assert!(check_roots(&root.join("pkg"), &allowed).is_ok());
+        assert!(matches!(check_roots(&sibling, &allowed), Err(FlowlensError::PathNotAllowed { .. })));
+    }
+
+    #[test]
+    fn check_roots_ignores_roots_that_do_not_exist() {
+        let tmp = TempDir::new("missing-root");
+        let repo = tmp.mkdir("repo");
+        let allowed = format!("{}:{}", tmp.0.join("gone").display(), repo.display());
+
+        assert!(check_roots(&repo, &allowed).is_ok());
+        let result = check_roots(&repo, &tmp.0.join("gone").display().to_string());
+        assert!(matches!(result, Err(FlowlensError::PathNotAllowed { ref allowed_roots, .. }) if allowed_roots.is_empty()));
+    }
+
+    #[test]
+    fn resolve_repo_path_rejects_dotdot_out_of_a_root() {
+        let tmp = TempDir::new("dotdot");
+        let root = tmp.mkdir("allowed");
+        tmp.mkdir("outside");
+        let allowed = root.display().to_string();
+
+        let escaped = format!("{}/../outside", root.display());
+        assert!(not_allowed(resolve_repo_path_in(&escaped, None, &allowed)));
+        let inside = format!("{}/../allowed", root.display());
+        assert_eq!(resolve_repo_path_in(&inside, None, &allowed).unwrap(), root);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolve_repo_path_follows_symlinks_before_checking_roots() {
+        let tmp = TempDir::new("symlink");
+        let root = tmp.mkdir("allowed");
+        let outside = tmp.mkdir("outside");
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+        std::os::unix::fs::symlink(&root, tmp.0.join("alias")).unwrap();
+        let allowed = root.display().to_string();
+
+        // A link inside the root that leads out of it is judged by its target
+        let link = root.join("link").display().to_string();
+        assert!(not_allowed(resolve_repo_path_in(&link, None, &allowed)));
+        // Likewise a link outside the root that leads into it
+        let alias = tmp.0.join("alias").display().to_string();
+        assert_eq!(resolve_repo_path_in(&alias, None, &allowed).unwrap(), root);
+        // And a root given through a link still covers its target
+        assert!(check_roots(&root, &alias).is_ok());
+    }
+
+    #[test]
+    fn resolve_repo_path_resolves_relative_paths_against_home() {
+        let tmp = TempDir::new("relative");
+        let repo = tmp.mkdir("code/repo");
+        let home = Some(tmp.0.clone());
+
+        assert_eq!(resolve_repo_path_in("code/repo", home.clone(), "").unwrap(), repo);
+        assert_eq!(resolve_repo_path_in("~/code/repo", home.clone(), "").unwrap(), repo);
+        assert_eq!(resolve_repo_path_in("~", home.clone(), "").unwrap(), tmp.0);
+        // Relative paths are checked against the roots after resolving
+        let allowed = tmp.mkdir("elsewhere").display().to_string();
+        assert!(not_allowed(resolve_repo_path_in("code/repo", home, &allowed)));
+    }
+
+    #[test]
+    fn resolve_repo_path_rejects_empty_missing_and_file_paths() {
+        let tmp = TempDir::new("invalid");
+        std::fs::write(tmp.0.join("file.py"), "").unwrap();
+        let invalid = |raw: &str| {
+            matches!(
+                resolve_repo_path_in(raw, Some(tmp.0.clone()), ""),
+                Err(FlowlensError::InvalidRepoPath { .. })
+            )
+        };
+
+        assert!(invalid("   "));
+        assert!(invalid("missing"));
+        assert!(invalid("file.py"));
+        assert!(matches!(
+            resolve_repo_path_in("relative", None, ""),
+            Err(FlowlensError::InvalidRepoPath { .. })
+        ));
+    }
+}
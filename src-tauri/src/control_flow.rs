@@ -0,0 +1,75 @@
+use serde_json::Value;
+use std::collections::HashMap;
+
+// ------------------------
+// Control Flow Tagging
+// ------------------------
+
+/// Classifies each trace event relative to the previous one so the UI can mark
+/// taken branches without asking Python:
+/// - `call`: execution entered a function not already on the stack
+/// - `return`: the frame returned, or execution resumed in a caller
+/// - `linear`: the next code line in the same function
+/// - `jump`: any other move within the same function (branch, loop back-edge)
+#[derive(Default)]
+pub struct ControlFlowTracker {
+    /// Function names from the entry function down to the current frame
+    stack: Vec<String>,
+    last_line: Option<u64>,
+    /// Per file, whether each line (index = line - 1) holds code rather than
+    /// a blank line or a comment
+    code_lines: HashMap<String, Vec<bool>>,
+}
+
+impl ControlFlowTracker {
+    pub fn classify(&mut self, event: &Value) -> &'static str {
+        let kind = event.get("event").and_then(Value::as_str).unwrap_or("line");
+        let function = event.get("function").and_then(Value::as_str).unwrap_or("").to_string();
+        let line = event.get("line").and_then(Value::as_u64);
+        let file = event.get("filename").and_then(Value::as_str).unwrap_or("");
+
+        let tag = if kind == "return" {
+            self.stack.pop();
+            "return"
+        } else if self.stack.last() != Some(&function) {
+            if let Some(depth) = self.stack.iter().rposition(|f| *f == function) {
+                self.stack.truncate(depth + 1);
+                "return"
+            } else {
+                self.stack.push(function);
+                "call"
+            }
+        } else {
+            match (self.last_line, line) {
+                (Some(prev), Some(line)) if self.next_code_line(file, prev) == Some(line) => "linear",
+                _ => "jump",
+            }
+        };
+
+        self.last_line = line;
+        tag
+    }
+
+    /// First line after `line` in `file` that holds code.
+    fn next_code_line(&mut self, file: &str, line: u64) -> Option<u64> {
+        let code = self
+            .code_lines
+            .entry(file.to_string())
+            .or_insert_with(|| {
+                std::fs::read_to_string(file)
+                    .map(|text| {
+                        text.lines()
+                            .map(|l| {
+                                let l = l.trim();
+                                !l.is_empty() && !l.starts_with('#')
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            });
+
+        (line as usize..code.len())
+            .find(|&idx| code[idx])
+            .map(|idx| idx as u64 + 1)
+    }
+}
@@ -0,0 +1,51 @@
+use serde_json::{json, Map, Value};
+
+// ------------------------
+// Event Deltas
+// ------------------------
+
+/// Fields diffed between steps rather than sent whole.
+const DIFFED_FIELDS: &[&str] = &["locals", "globals"];
+
+/// `next` as a change against `previous`, the last full event the UI has.
+/// Everything but `locals`/`globals` is copied as is; those two become
+/// `{added, changed, removed}`, so applying them to `previous` rebuilds `next`.
+/// Marked with `event_type: "delta"`.
+pub fn encode(previous: &Value, next: &Value) -> Value {
+    let Value::Object(fields) = next else {
+        return next.clone();
+    };
+    let mut delta = Map::new();
+    for (key, value) in fields {
+        if DIFFED_FIELDS.contains(&key.as_str()) {
+            let empty = Map::new();
+            let before = previous.get(key).and_then(Value::as_object).unwrap_or(&empty);
+            let after = value.as_object().unwrap_or(&empty);
+            delta.insert(key.clone(), diff(before, after));
+        } else {
+            delta.insert(key.clone(), value.clone());
+        }
+    }
+    delta.insert("event_type".to_string(), json!("delta"));
+    Value::Object(delta)
+}
+
+/// `after` against `before` as `{added, changed, removed}`: new and changed
+/// values in full, removed names only.
+pub fn diff(before: &Map<String, Value>, after: &Map<String, Value>) -> Value {
+    let mut added = Map::new();
+    let mut changed = Map::new();
+    for (name, value) in after {
+        match before.get(name) {
+            None => {
+                added.insert(name.clone(), value.clone());
+            }
+            Some(old) if old != value => {
+                changed.insert(name.clone(), value.clone());
+            }
+            Some(_) => {}
+        }
+    }
+    let removed: Vec<&String> = before.keys().filter(|name| !after.contains_key(*name)).collect();
+    json!({ "added": added, "changed": changed, "removed": removed })
+}
@@ -0,0 +1,174 @@
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+
+// ------------------------
+// Command Errors
+// ------------------------
+
+/// Errors returned to the frontend. Serialized with a `kind` tag so the UI
+/// can branch on the failure instead of parsing message text.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FlowlensError {
+    InvalidRepoPath { path: String, reason: String },
+    PathNotAllowed { path: String, allowed_roots: Vec<String> },
+    /// Changed-function flows come from `git diff`; the file tree and tracing work without git
+    NotAGitRepo { path: String },
+    InvalidRequest { message: String },
+    NoActiveTracer { entry_full_id: String },
+    /// The traced function is still running (or paused), so there is no result yet
+    NoReturnYet { entry_full_id: String },
+    /// The tracer has not produced a step event yet
+    NoCurrentEvent { entry_full_id: String },
+    /// `stop_line` is not inside the entry function, so the tracer would never stop there
+    BreakpointOutOfRange {
+        entry_full_id: String,
+        stop_line: i32,
+        start_line: u32,
+        end_line: u32,
+    },
+    /// Starting another flow needs a tracer stopped, but every live one is pinned
+    TracerPinned { pinned_entry_full_id: String },
+    Timeout { operation: String, timeout_ms: u64 },
+    /// The tracer process is gone; `output` is what it wrote on the diagnostics channel.
+    /// `code` is set when it exited on its own, `signal` when it was killed
+    /// (a crash, the OOM killer, `kill`).
+    PythonExited {
+        code: Option<i32>,
+        signal: Option<i32>,
+        message: String,
+        output: String,
+    },
+    /// The interpreter the app picked could not be started
+    PythonNotFound { python: String },
+    /// The interpreter runs but is older than the tools support
+    PythonTooOld {
+        python: String,
+        version: String,
+        minimum: String,
+    },
+    /// The repo directory doesn't exist: none was set and the default is missing, or it was removed
+    RepoNotConfigured { path: String },
+    /// A Python tool the app runs is missing from the tools dir
+    ScriptNotFound { script: String, tools_dir: String },
+    /// A one-shot Python tool or git exited unsuccessfully, or could not be
+    /// started. `stderr` falls back to stdout when the script reported its
+    /// error there.
+    ScriptFailed {
+        script: String,
+        code: Option<i32>,
+        stderr: String,
+    },
+    /// A Python tool printed something that isn't the JSON the app expects
+    InvalidJson { message: String },
+    /// Reading or writing a file of the app's (settings, presets, exports) failed,
+    /// or one of its directories could not be determined
+    Io { path: String, message: String },
+    /// Any other tracer failure, described for humans
+    Tracer { message: String },
+    /// `shutdown` ran; no command works after it
+    ShuttingDown,
+}
+
+impl fmt::Display for FlowlensError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlowlensError::InvalidRepoPath { path, reason } => {
+                write!(f, "invalid repo path {}: {}", path, reason)
+            }
+            FlowlensError::PathNotAllowed { path, allowed_roots } => write!(
+                f,
+                "repo path {} is not under an allowed root ({})",
+                path,
+                allowed_roots.join(":")
+            ),
+            FlowlensError::NotAGitRepo { path } => write!(
+                f,
+                "{} is not a git repository, so there are no changed functions to list; \
+                 run `git init` and commit to use flows, or browse the file tree and trace functions directly",
+                path
+            ),
+            FlowlensError::InvalidRequest { message } => write!(f, "invalid request: {}", message),
+            FlowlensError::NoActiveTracer { entry_full_id } => {
+                write!(f, "no active tracer for {}", entry_full_id)
+            }
+            FlowlensError::NoReturnYet { entry_full_id } => {
+                write!(f, "{} has not returned yet", entry_full_id)
+            }
+            FlowlensError::NoCurrentEvent { entry_full_id } => {
+                write!(f, "no event has been traced for {} yet", entry_full_id)
+            }
+            FlowlensError::BreakpointOutOfRange {
+                entry_full_id,
+                stop_line,
+                start_line,
+                end_line,
+            } => write!(
+                f,
+                "line {} is outside {} (lines {}-{})",
+                stop_line, entry_full_id, start_line, end_line
+            ),
+            FlowlensError::TracerPinned { pinned_entry_full_id } => {
+                write!(
+                    f,
+                    "all tracers are pinned (e.g. the one for {}); unpin or close one to trace another flow",
+                    pinned_entry_full_id
+                )
+            }
+            FlowlensError::Timeout { operation, timeout_ms } => {
+                write!(f, "timed out after {}ms {}", timeout_ms, operation)
+            }
+            FlowlensError::PythonExited { message, output, .. } => {
+                write!(f, "{}", message)?;
+                if !output.is_empty() {
+                    write!(f, "\n{}", output)?;
+                }
+                Ok(())
+            }
+            FlowlensError::PythonNotFound { python } => write!(
+                f,
+                "could not run Python at {}; install it, set PYTHON_BIN or create a venv in the repo, then call refresh_python",
+                python
+            ),
+            FlowlensError::PythonTooOld { python, version, minimum } => write!(
+                f,
+                "Python at {} is version {}, but {} or newer is needed; set PYTHON_BIN or create a venv in the repo, then call refresh_python",
+                python, version, minimum
+            ),
+            FlowlensError::RepoNotConfigured { path } => {
+                write!(f, "repo {} does not exist; choose a repo first", path)
+            }
+            FlowlensError::ScriptNotFound { script, tools_dir } => {
+                write!(f, "{} is missing from the tools dir {}", script, tools_dir)
+            }
+            FlowlensError::ScriptFailed { script, code, stderr } => {
+                match code {
+                    Some(code) => write!(f, "{} exited with code {}", script, code)?,
+                    None => write!(f, "{} failed without an exit code", script)?,
+                }
+                if !stderr.is_empty() {
+                    write!(f, ": {}", stderr.trim_end())?;
+                }
+                Ok(())
+            }
+            FlowlensError::InvalidJson { message } => write!(f, "invalid json: {}", message),
+            FlowlensError::Io { path, message } => write!(f, "{}: {}", path, message),
+            FlowlensError::Tracer { message } => write!(f, "{}", message),
+            FlowlensError::ShuttingDown => write!(f, "the app has shut down its background work"),
+        }
+    }
+}
+
+impl std::error::Error for FlowlensError {}
+
+impl FlowlensError {
+    /// `Io` for a failure reading or writing `path`.
+    pub fn io(path: &Path, err: impl fmt::Display) -> Self {
+        FlowlensError::Io {
+            path: path.display().to_string(),
+            message: err.to_string(),
+        }
+    }
+}
+
@@ -0,0 +1,64 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::process::{Command, Output};
+use std::sync::Mutex;
+
+use crate::process::{argv, exit_signal};
+
+// ------------------------
+// Failed Analysis Runs
+// ------------------------
+
+/// Everything a failed Python analysis run left behind, for bug reports.
+#[derive(Serialize, Clone, Debug)]
+pub struct Failure {
+    pub argv: Vec<String>,
+    pub exit_code: Option<i32>,
+    /// Set instead of `exit_code` when the process was killed
+    pub signal: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    /// What the command reported, e.g. that stdout was not valid JSON
+    pub error: String,
+}
+
+impl Failure {
+    pub fn new(cmd: &Command, output: &Output, error: &str) -> Self {
+        Self {
+            argv: argv(cmd),
+            exit_code: output.status.code(),
+            signal: exit_signal(output.status),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            error: error.to_string(),
+        }
+    }
+}
+
+/// The last failure of each kind of analysis run (e.g. `get_flows`), until a
+/// run of that kind succeeds.
+#[derive(Default)]
+pub struct Failures {
+    last: HashMap<String, Failure>,
+}
+
+impl Failures {
+    /// Record the outcome of a `kind` run: keep `failure`, or forget the
+    /// previous one when the run succeeded.
+    pub fn record(&mut self, kind: &str, failure: Option<Failure>) {
+        match failure {
+            Some(failure) => {
+                self.last.insert(kind.to_string(), failure);
+            }
+            None => {
+                self.last.remove(kind);
+            }
+        }
+    }
+
+    pub fn get(&self, kind: &str) -> Option<&Failure> {
+        self.last.get(kind)
+    }
+}
+
+pub type SharedFailures = Mutex<Failures>;
@@ -1,11 +1,56 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
-use serde_json::{json, Value};
-use serde::Deserialize;
-use std::io::{BufReader, Write};
-use std::process::{Child, ChildStdin, ChildStdout, ChildStderr, Command, Stdio};
-use tauri::State;
-use std::sync::Mutex;
+use serde_json::{json, Map, Value};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, ExitStatus, Output, Stdio};
+use tauri::{AppHandle, Emitter, Manager, RunEvent, State};
+use std::sync::mpsc::{self, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
+mod config;
+mod control_flow;
+mod delta;
+mod error;
+mod failures;
+mod orphans;
+mod paths;
+mod payload;
+mod pending;
+mod persist;
+mod presets;
+mod process;
+mod protocol;
+mod recent;
+mod settings;
+mod stream;
+mod tools;
+mod watch;
+mod worktree;
+
+use config::{check_allowed_root, missing_tools, resolve_repo_path, resolve_sys_path, AppConfig, DefaultStop, EventChannel, PythonInterpreter, SharedConfig, TracerBackend, MAX_CONTEXT_LINES, MIN_READ_TIMEOUT_SECS};
+use control_flow::ControlFlowTracker;
+use error::FlowlensError;
+use failures::{Failure, Failures, SharedFailures};
+use payload::{enforce_limits, PayloadLimits};
+use orphans::TracerProcess;
+use paths::RepoPaths;
+use pending::PendingCommands;
+use presets::{BreakpointPreset, BreakpointPresets, SharedBreakpointPresets};
+use process::{checked_output, describe_exit, exit_signal, kill_and_reap_tracer, log_exit, output_logged, spawn_logged, try_reap_tracer};
+use protocol::{Phase, Receiver};
+use recent::{RecentFlow, RecentFlows, SharedRecentFlows};
+use settings::{Settings, SharedSettings};
+use stream::TraceStreams;
+use tools::{ToolSpec, TOOLS};
+use watch::{RepoWatcher, SharedRepoWatcher};
+use worktree::Worktree;
 
 #[tauri::command]
 fn greet(name: &str) -> String {
@@ -14,32 +59,245 @@ fn greet(name: &str) -> String {
 }
 
 #[tauri::command]
-fn get_flows() -> Result<Value, String> {
-    println!("[flowlens] get_flows: starting");
+fn set_repo_path(path: String, config: State<SharedConfig>) -> Result<Value, FlowlensError> {
+    println!("[flowlens] set_repo_path: {}", path);
 
-    let repo = "/home/bimal/Documents/ucsd/research/code/trap";
-    let python = std::env::var("PYTHON_BIN").unwrap_or_else(|_| "python3".to_string());
-    let script_path = "../tools/get_changed_functions.py";
+    let repo = resolve_repo_path(&path)?;
+    println!("[flowlens] set_repo_path: resolved to {}", repo.display());
 
-    let output = Command::new(&python)
-        .arg(script_path)
-        .arg("--repo")
-        .arg(&repo)
-        .output()
-        .map_err(|e| format!("failed to run python: {}", e))?;
+    let mut config = config.lock().unwrap();
+    config.repo_path = Some(repo.clone());
+    config.demo = false;
+    config.sys_path.clear();
+    config.refresh_python();
+    Ok(json!({ "repo": repo }))
+}
+
+/// Demo project bundled as a Tauri resource (see `bundle.resources`).
+const DEMO_DIR: &str = "demo";
+
+/// The Python tools inside the app's resource dir, per `bundle.resources`.
+const BUNDLED_TOOLS_DIR: &str = "tools";
+
+/// Precomputed `get_flows` result shipped with the demo, in place of git diff output.
+const DEMO_FLOWS_JSON: &str = "flows.json";
+
+/// Point every command at the bundled demo repo so new users can try the
+/// whole flow before configuring their own. The demo is read-only: it ships
+/// inside the app bundle, and flows come from its precomputed flows.json.
+#[tauri::command]
+fn use_demo_repo(app: AppHandle, config: State<SharedConfig>) -> Result<Value, FlowlensError> {
+    println!("[flowlens] use_demo_repo");
+
+    let demo = app
+        .path()
+        .resource_dir()
+        .map(|dir| dir.join(DEMO_DIR))
+        .map_err(|e| FlowlensError::InvalidRepoPath {
+            path: DEMO_DIR.to_string(),
+            reason: format!("could not locate app resources: {}", e),
+        })?;
+    if !demo.join(DEMO_FLOWS_JSON).is_file() {
+        return Err(FlowlensError::InvalidRepoPath {
+            path: demo.display().to_string(),
+            reason: "demo repo is missing from the app bundle".to_string(),
+        });
+    }
+
+    let mut config = config.lock().unwrap();
+    config.repo_path = Some(demo.clone());
+    config.demo = true;
+    config.sys_path.clear();
+    config.refresh_python();
+    Ok(json!({ "repo": demo, "demo": true }))
+}
+
+fn read_demo_flows(repo: &Path) -> Result<Value, FlowlensError> {
+    let text = std::fs::read_to_string(repo.join(DEMO_FLOWS_JSON))
+        .map_err(|e| FlowlensError::io(&repo.join(DEMO_FLOWS_JSON), e))?;
+    serde_json::from_str(&text).map_err(|e| FlowlensError::InvalidJson { message: e.to_string() })
+}
+
+/// Fail with `RepoNotConfigured` when the repo directory is missing, before a
+/// tool runs against it and reports something less obvious, and with
+/// `PathNotAllowed` when it is outside `FLOWLENS_ALLOWED_ROOTS`. That covers
+/// the default repo and the demo too, which don't go through `resolve_repo_path`.
+fn require_repo(config: &AppConfig) -> Result<PathBuf, FlowlensError> {
+    let repo = config.repo();
+    if !repo.is_dir() {
+        return Err(FlowlensError::RepoNotConfigured {
+            path: repo.display().to_string(),
+        });
+    }
+    // A worktree for `at_commit` lives in the temp dir; the repo it came from is what's checked
+    let checked = config.checkout_of.as_deref().unwrap_or(&repo);
+    check_allowed_root(&checked.canonicalize().unwrap_or_else(|_| checked.to_path_buf()))?;
+    Ok(repo)
+}
+
+/// The error for a Python child that could not be started at all.
+fn python_spawn_error(cmd: &Command, err: std::io::Error) -> FlowlensError {
+    if err.kind() == std::io::ErrorKind::NotFound {
+        return FlowlensError::PythonNotFound {
+            python: cmd.get_program().to_string_lossy().into_owned(),
+        };
+    }
+    FlowlensError::Tracer {
+        message: format!("failed to run python: {}", err),
+    }
+}
 
-    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+/// Run a one-shot Python tool to completion, whatever its exit status.
+fn run_tool(cmd: &mut Command) -> Result<Output, FlowlensError> {
+    output_logged(cmd).map_err(|e| python_spawn_error(cmd, e))
+}
+
+/// `ScriptFailed` for a tool that exited unsuccessfully. Most tools print
+/// their error JSON on stdout, so that stands in for an empty stderr.
+fn script_failed(script: &str, output: &Output) -> FlowlensError {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stderr = if stderr.trim().is_empty() {
+        String::from_utf8_lossy(&output.stdout)
+    } else {
+        stderr
+    };
+    FlowlensError::ScriptFailed {
+        script: script.to_string(),
+        code: output.status.code(),
+        stderr: stderr.into_owned(),
+    }
+}
 
+/// Parse a successful tool's stdout, or `script_failed` when it didn't succeed.
+fn tool_json<T: DeserializeOwned>(script: &str, output: &Output) -> Result<T, FlowlensError> {
     if !output.status.success() {
-        return Err(format!("python script error: {}", stdout));
+        return Err(script_failed(script, output));
+    }
+    serde_json::from_slice(&output.stdout).map_err(|e| FlowlensError::InvalidJson {
+        message: format!("{} printed {}: {}", script, e, String::from_utf8_lossy(&output.stdout)),
+    })
+}
+
+/// Written by get_changed_functions.py into the working directory.
+const FUNCTIONS_JSON: &str = "functions.json";
+
+/// Run get_changed_functions.py with `extra_args` (e.g. `--names-only`) and parse
+/// its output. The outcome is recorded in `failures` under `kind`.
+fn run_changed_functions(config: &AppConfig, extra_args: &[&str], kind: &str, failures: &SharedFailures) -> Result<Value, FlowlensError> {
+    let repo = require_repo(config)?;
+    let python = config.checked_python()?.path;
+    let script_path = config.resolve_script(tools::CHANGED_FUNCTIONS)?;
+
+    let mut cmd = config.python_command(&python);
+    cmd.arg(script_path).arg("--repo").arg(repo).args(extra_args);
+    let output = run_tool(&mut cmd)?;
+
+    let result = tool_json(tools::CHANGED_FUNCTIONS, &output);
+    failures.lock().unwrap().record(kind, result.as_ref().err().map(|e| Failure::new(&cmd, &output, &e.to_string())));
+    result
+}
+
+/// Most recent modification time of any Python file in the repo.
+fn newest_source_mtime(dir: &Path) -> Option<SystemTime> {
+    let mut newest = None;
+    for entry in std::fs::read_dir(dir).ok()?.flatten() {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with('.') || name == "__pycache__" || name == "venv" || name == "node_modules" {
+            continue;
+        }
+        let mtime = if path.is_dir() {
+            newest_source_mtime(&path)
+        } else if name.ends_with(".py") {
+            entry.metadata().and_then(|m| m.modified()).ok()
+        } else {
+            None
+        };
+        newest = newest.max(mtime);
+    }
+    newest
+}
+
+/// functions.json is stale when some source file changed after it was written.
+/// A missing file is not stale: there is simply nothing cached to trust.
+fn functions_json_is_stale(repo: &Path) -> bool {
+    let Ok(written) = std::fs::metadata(FUNCTIONS_JSON).and_then(|m| m.modified()) else {
+        return false;
+    };
+    newest_source_mtime(repo).is_some_and(|newest| newest > written)
+}
+
+/// Whether `repo` is inside a git work tree. False when git isn't installed.
+fn is_git_repo(repo: &Path) -> bool {
+    output_logged(Command::new("git").arg("-C").arg(repo).args(["rev-parse", "--is-inside-work-tree"]))
+        .is_ok_and(|output| output.status.success())
+}
+
+/// Fail with `NotAGitRepo` unless the repo has git history to diff. The demo
+/// ships precomputed flows, so it never needs git.
+fn require_git(config: &AppConfig) -> Result<(), FlowlensError> {
+    let repo = config.repo();
+    if config.demo || is_git_repo(&repo) {
+        return Ok(());
+    }
+    Err(FlowlensError::NotAGitRepo {
+        path: repo.display().to_string(),
+    })
+}
+
+/// Ids of the changed functions in the last `get_flows` result, sorted, for
+/// `trace_next_changed` to pick from.
+type SharedChangedFunctions = Mutex<Vec<String>>;
+
+#[tauri::command]
+fn get_flows(
+    refresh_if_stale: Option<bool>,
+    config: State<SharedConfig>,
+    failures: State<SharedFailures>,
+    changed: State<SharedChangedFunctions>,
+) -> Result<Value, FlowlensError> {
+    println!("[flowlens] get_flows: starting");
+
+    let config = config.lock().unwrap().clone();
+    require_git(&config)?;
+    let flows = load_flows(refresh_if_stale.unwrap_or(false), &config, &failures)?;
+    *changed.lock().unwrap() = flows["functions"]
+        .as_object()
+        .map(|functions| functions.keys().cloned().collect())
+        .unwrap_or_default();
+    Ok(flows)
+}
+
+fn load_flows(refresh_if_stale: bool, config: &AppConfig, failures: &SharedFailures) -> Result<Value, FlowlensError> {
+    let repo = config.repo();
+    if config.demo {
+        let flows = read_demo_flows(&repo)?;
+        return Ok(json!({
+            "parents": flows["parents"],
+            "functions": flows["functions"],
+            "stale": false
+        }));
+    }
+
+    // Checked before the script runs: it rewrites functions.json, which would
+    // make the file look fresh while still holding outdated entries
+    let mut stale = functions_json_is_stale(&repo);
+    if stale && refresh_if_stale {
+        // The script only rewrites entries for currently changed functions,
+        // so start from an empty file to drop everything outdated
+        println!("[flowlens] get_flows: functions.json is stale, regenerating");
+        let _ = std::fs::remove_file(FUNCTIONS_JSON);
     }
 
     // Load script output (parents)
-    let parents_json: Value = serde_json::from_str(&stdout)
-        .map_err(|e| format!("invalid json: {}", e))?;
+    let parents_json = run_changed_functions(config, &[], "get_flows", failures)?;
+    if refresh_if_stale {
+        stale = functions_json_is_stale(&repo);
+    }
 
     // Load functions.json saved by Python script
-    let functions_json = std::fs::read_to_string("functions.json")
+    let functions_json = std::fs::read_to_string(FUNCTIONS_JSON)
         .unwrap_or_else(|_| "{}".to_string());
     let functions: Value = serde_json::from_str(&functions_json)
         .unwrap_or(Value::Null);
@@ -47,295 +305,3918 @@ fn get_flows() -> Result<Value, String> {
     // Combine result
     let combined = json!({
         "parents": parents_json["parents"],
-        "functions": functions
+        "functions": functions,
+        "stale": stale
     });
 
     Ok(combined)
 }
 
+/// Changed function ids with their file and def line, without bodies or the
+/// call graph, for a fast first sidebar render. Details are fetched per function.
 #[tauri::command]
-fn get_file_tree() -> Result<Value, String> {
-    println!("[flowlens] get_file_tree");
+fn list_changed_functions(
+    base_ref: Option<String>,
+    config: State<SharedConfig>,
+    failures: State<SharedFailures>,
+) -> Result<Value, FlowlensError> {
+    println!("[flowlens] list_changed_functions (base={:?})", base_ref);
 
-    let repo = "/home/bimal/Documents/ucsd/research/code/trap";
-    let python = std::env::var("PYTHON_BIN").unwrap_or("python3".to_string());
-    let script_path = "../tools/get_file_tree.py";
+    let config = config.lock().unwrap().clone();
+    require_git(&config)?;
+    if config.demo {
+        let flows = read_demo_flows(&config.repo())?;
+        let listed: Vec<Value> = flows["functions"]
+            .as_object()
+            .into_iter()
+            .flatten()
+            .map(|(id, f)| json!({ "entry_full_id": id, "file": f["file_path"], "line": f["start_line"] }))
+            .collect();
+        return Ok(json!(listed));
+    }
 
-    let output = Command::new(&python)
-        .arg(script_path)
-        .arg("--root")
-        .arg(repo)
-        .output()
-        .map_err(|e| format!("failed to run python: {}", e))?;
+    let mut args = vec!["--names-only"];
+    if let Some(base_ref) = base_ref.as_deref().filter(|r| !r.is_empty()) {
+        if base_ref.starts_with('-') {
+            return Err(FlowlensError::InvalidRequest {
+                message: format!("invalid base ref: {}", base_ref),
+            });
+        }
+        args.extend(["--base_ref", base_ref]);
+    }
 
-    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-    if !output.status.success() {
-        return Err(format!("python error: {}", stdout));
+    let listed = run_changed_functions(&config, &args, "list_changed_functions", &failures)?;
+    Ok(listed["functions"].clone())
+}
+
+fn run_file_tree(config: &AppConfig, with_metadata: bool, failures: &SharedFailures) -> Result<Value, FlowlensError> {
+    let repo = require_repo(config)?;
+    let python = config.checked_python()?.path;
+    let script_path = config.resolve_script(tools::FILE_TREE)?;
+
+    let mut cmd = config.python_command(&python);
+    cmd.arg(script_path).arg("--root").arg(&repo);
+    if with_metadata {
+        cmd.arg("--with-metadata");
     }
+    let output = run_tool(&mut cmd)?;
+
+    let result = tool_json::<Value>(tools::FILE_TREE, &output);
+    failures.lock().unwrap().record("get_file_tree", result.as_ref().err().map(|e| Failure::new(&cmd, &output, &e.to_string())));
 
-    let tree: Value = serde_json::from_str(&stdout)
-        .map_err(|e| format!("invalid json: {}", e))?;
+    let mut tree = result?;
+    RepoPaths::new(&repo).relativize(&mut tree);
     Ok(tree)
 }
 
+/// Walking a large repo can take seconds, so the Python child runs on the
+/// blocking pool and the command awaits it instead of holding a worker that
+/// stepping commands need.
+///
+/// With `with_metadata`, files also carry `size` (bytes) and `mtime` (Unix
+/// seconds) and folders `child_count`. That stats every file, which adds
+/// noticeably to the walk on large trees, so it is off by default.
+#[tauri::command]
+async fn get_file_tree(
+    app: AppHandle,
+    with_metadata: Option<bool>,
+    config: State<'_, SharedConfig>,
+) -> Result<Value, FlowlensError> {
+    println!("[flowlens] get_file_tree (with_metadata={:?})", with_metadata);
+
+    let config = config.lock().unwrap().clone();
+    let with_metadata = with_metadata.unwrap_or(false);
+    tauri::async_runtime::spawn_blocking(move || run_file_tree(&config, with_metadata, &app.state::<SharedFailures>()))
+        .await
+        .map_err(|e| FlowlensError::Tracer {
+            message: format!("file tree task failed: {}", e),
+        })?
+}
+
+/// Watch the repo for file changes, replacing any previous watch. When a
+/// burst of edits settles, cached analysis that may now be wrong is dropped
+/// (callers, callees, coverage, the changed-function list and the tracer's entry span)
+/// and `repo-changed {paths}` is emitted so the UI can refetch. `functions.json`
+/// needs no help: `get_flows` already reports it stale by mtime.
+///
+/// Changes come from the OS's file events, so watching costs nothing while
+/// the repo is idle. Call again after `set_repo_path` to follow the new repo.
+#[tauri::command]
+fn watch_repo(app: AppHandle, config: State<SharedConfig>, watcher: State<SharedRepoWatcher>) -> Result<Value, FlowlensError> {
+    let repo = config.lock().unwrap().repo();
+    println!("[flowlens] watch_repo: {}", repo.display());
+
+    let started = RepoWatcher::start(&repo, move |paths| {
+        println!("[flowlens] repo changed: {} path(s)", paths.len());
+        app.state::<SharedCallersCache>().lock().unwrap().clear();
+        app.state::<SharedCalleesCache>().lock().unwrap().clear();
+        app.state::<SharedCoverage>().lock().unwrap().clear();
+        app.state::<SharedChangedFunctions>().lock().unwrap().clear();
+        // Never waits on a step: neither lock is held for long
+        for (_, slot) in tracer_slots(&app.state::<SharedTracer>()) {
+            *slot.source_span.lock().unwrap() = None;
+        }
+        if let Err(e) = app.emit("repo-changed", json!({ "paths": paths })) {
+            println!("[flowlens] failed to emit repo-changed: {}", e);
+        }
+    })?;
+    *watcher.lock().unwrap() = Some(started);
+    Ok(json!({ "repo": repo, "watching": true }))
+}
+
+/// Stop watching the repo. Returns whether a watch was running.
+#[tauri::command]
+fn unwatch_repo(watcher: State<SharedRepoWatcher>) -> bool {
+    let stopped = watcher.lock().unwrap().take();
+    if let Some(watcher) = &stopped {
+        println!("[flowlens] unwatch_repo: {}", watcher.root().display());
+    }
+    stopped.is_some()
+}
+
+/// Full record of the last failed `kind` run (`get_flows`,
+/// `list_changed_functions` or `get_file_tree`): argv, exit status and both
+/// output streams, for bug reports. `None` once a run of that kind succeeds.
+#[tauri::command]
+fn last_failure(kind: String, failures: State<SharedFailures>) -> Option<Failure> {
+    failures.lock().unwrap().get(&kind).cloned()
+}
 
 
 
 // ------------------------
 // Shared Tracer State
 // ------------------------
+/// `event` of the marker Python writes after the last event of each step.
+const STEP_END_EVENT: &str = "step_end";
+
+/// How many stdin writes may be queued before callers are turned away.
+const STDIN_QUEUE_DEPTH: usize = 4;
+
+/// How long a caller waits for its stdin write to reach Python.
+const STDIN_WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Wait before the first spawn retry, multiplied by the attempt number.
+const SPAWN_RETRY_BACKOFF: Duration = Duration::from_millis(200);
+
+/// A line queued for the tracer's stdin, plus where to report the write result.
+struct StdinCommand {
+    line: String,
+    done: mpsc::Sender<std::io::Result<()>>,
+}
+
+/// Own the child's stdin on a dedicated thread so a Python process that stops
+/// draining its pipe blocks that thread instead of the command thread. The
+/// thread exits when the sender is dropped or a write fails (e.g. the child died).
+fn spawn_stdin_writer(mut stdin: ChildStdin) -> SyncSender<StdinCommand> {
+    let (tx, rx) = mpsc::sync_channel::<StdinCommand>(STDIN_QUEUE_DEPTH);
+    thread::spawn(move || {
+        for cmd in rx {
+            let result = writeln!(stdin, "{}", cmd.line).and_then(|_| stdin.flush());
+            let failed = result.is_err();
+            let _ = cmd.done.send(result);
+            if failed {
+                break;
+            }
+        }
+    });
+    tx
+}
+
+/// A line from the events channel, newline included; `Ok` of an empty vec at EOF.
+type EventLine = std::io::Result<Vec<u8>>;
+
+/// How long `kill` waits for each output reader thread to exit.
+const READER_JOIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Newest lines of the diagnostics channel, shared with the thread draining it.
+type DiagnosticLines = Arc<Mutex<VecDeque<String>>>;
+
+/// Called with the channel (`events` or `diagnostics`) and text of each new
+/// log line; `tail_tracer_logs` emits them as `tracer-log`.
+type LogSink = Arc<dyn Fn(&str, &str) + Send + Sync>;
+
+/// The tracer's `LogSink`, if any, shared with the diagnostics reader thread.
+type LogTail = Arc<Mutex<Option<LogSink>>>;
+
+/// Drain the diagnostics channel on a dedicated thread, keeping the newest
+/// `cap` lines and passing each to the `tail` sink, so Python never blocks on
+/// a full pipe and its warnings and tracebacks can be read while it runs. The
+/// thread exits at EOF or on a read error.
+fn spawn_diagnostics_reader(
+    diagnostics: Box<dyn Read + Send>,
+    cap: usize,
+    tail: LogTail,
+) -> (DiagnosticLines, thread::JoinHandle<()>) {
+    let lines = DiagnosticLines::default();
+    let shared = lines.clone();
+    let reader = thread::spawn(move || {
+        for line in BufReader::new(diagnostics).split(b'\n') {
+            let Ok(line) = line else {
+                break;
+            };
+            let line = String::from_utf8_lossy(&line).trim_end().to_string();
+            // Emitted under the buffer's lock, so `tail_tracer_logs` neither misses nor repeats a line
+            let mut lines = shared.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            if let Some(sink) = &*tail.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) {
+                sink("diagnostics", &line);
+            }
+            lines.push_back(line);
+            while lines.len() > cap {
+                lines.pop_front();
+            }
+        }
+    });
+    (lines, reader)
+}
+
+/// Wait up to `READER_JOIN_TIMEOUT` for a reader thread whose pipe should be
+/// at EOF. A process the traced code started may have inherited the pipe and
+/// keep it open; then the thread is left to end with that process.
+fn join_reader(reader: thread::JoinHandle<()>, what: &str) {
+    let deadline = Instant::now() + READER_JOIN_TIMEOUT;
+    while !reader.is_finished() && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+    if reader.is_finished() {
+        let _ = reader.join();
+    } else {
+        println!("[flowlens] {} reader still blocked after {:?}; detaching it", what, READER_JOIN_TIMEOUT);
+    }
+}
+
+/// Read the events channel on a dedicated thread so a read can give up after a
+/// timeout instead of blocking on a hung tracer. The thread exits at EOF or on
+/// the first read error, both of which are passed on.
+fn spawn_event_reader(mut events: Box<dyn BufRead + Send>) -> (mpsc::Receiver<EventLine>, thread::JoinHandle<()>) {
+    let (tx, rx) = mpsc::channel();
+    let reader = thread::spawn(move || loop {
+        let mut bytes = Vec::new();
+        let result = events.read_until(b'\n', &mut bytes).map(|_| bytes);
+        let last = !matches!(&result, Ok(bytes) if !bytes.is_empty());
+        if tx.send(result).is_err() || last {
+            break;
+        }
+    });
+    (rx, reader)
+}
+
 struct Tracer {
     child: Child,
-    stdin: ChildStdin,
-    stdout: BufReader<ChildStdout>,
-    stderr: BufReader<std::process::ChildStderr>,
+    stdin: SyncSender<StdinCommand>,
+    /// Lines of the channel Python writes JSON events to, from `spawn_event_reader`
+    events: mpsc::Receiver<EventLine>,
+    /// The thread behind `events`; joined by `kill`
+    reader: Option<thread::JoinHandle<()>>,
+    /// How long a read waits for the next line; from config, updated by `set_read_timeout`
+    read_timeout: Duration,
+    /// Steps whose read timed out; their events are skipped when they arrive
+    skip_steps: usize,
+    /// Newest lines of the other channel (tracebacks, warnings and whatever the
+    /// traced code prints), up to `tracer_log_lines`
+    diagnostics: DiagnosticLines,
+    /// The thread filling `diagnostics`; joined by `kill` and `exited_error`
+    diagnostics_reader: Option<thread::JoinHandle<()>>,
+    events_on: EventChannel,
     current_flow: Option<String>,
+    control_flow: ControlFlowTracker,
+    /// Set when no venv was found; attached to the first event only
+    python_warning: Option<String>,
+    at_commit: Option<String>,
+    /// Checkout the tracer runs in when `at_commit` is set; held only so it is
+    /// removed when the tracer is dropped
+    _worktree: Option<Worktree>,
+    /// Spawned by `prewarm_tracer` and still waiting for `start`
+    prewarmed: bool,
+    /// What the entry function returned, once a `completed` return event arrives
+    return_value: Option<Value>,
+    /// Root the tracer runs in (the worktree for `at_commit`), for relativizing event paths
+    paths: RepoPaths,
+    /// `locals` of the latest step event and of the one before it
+    current_locals: Option<Map<String, Value>>,
+    previous_locals: Option<Map<String, Value>>,
+    /// `line` of the latest step event that had locals
+    current_line: Option<u64>,
+    /// Every step event so far, intermediate ones included
+    history: Vec<Step>,
+    /// The latest step event exactly as it was returned to the UI
+    last_event: Option<Value>,
+    /// Last line read from the events channel, cut to `raw_line_cap` bytes
+    last_raw_line: String,
+    /// Full length of `last_raw_line` before cutting
+    last_raw_len: usize,
+    raw_line_cap: usize,
+    /// Newest non-JSON lines that showed up on the events channel, oldest first
+    log: VecDeque<String>,
+    log_cap: usize,
+    /// Older lines were dropped from `log` to stay within `log_cap`
+    log_truncated: bool,
+    /// Set by `tail_tracer_logs`; each new `log` and `diagnostics` line is also emitted as `tracer-log`
+    log_tail: LogTail,
+    /// Set by `set_local_filter`; applied to the locals of every event returned
+    local_filter: LocalFilter,
+    /// Where the tracer pauses besides `stop_line`; from the request, then `update_breakpoints`
+    breakpoints: Vec<Breakpoint>,
+}
+
+/// One step event the trace produced, kept for fingerprints and exports.
+struct Step {
+    function: String,
+    line: u64,
+    /// Repo-relative, or absolute for files outside the repo
+    file: String,
+    phase: Phase,
+    /// Wall time of the read that returned it; 0 for intermediate events,
+    /// which arrive with the step they precede
+    step_ms: u64,
+}
+
+/// Which locals events show. Names in `exclude` are always dropped; when
+/// `include_only` is set, everything not listed is dropped too.
+#[derive(Default)]
+struct LocalFilter {
+    exclude: Vec<String>,
+    include_only: Option<Vec<String>>,
+}
+
+impl LocalFilter {
+    fn keeps(&self, name: &str) -> bool {
+        !self.exclude.iter().any(|n| n == name)
+            && self.include_only.as_ref().is_none_or(|only| only.iter().any(|n| n == name))
+    }
+
+    /// Drop filtered-out locals from `event` and its intermediate events,
+    /// noting how many were hidden as `locals_hidden`.
+    fn apply(&self, event: &mut Value) {
+        if let Some(steps) = event.get_mut("intermediate_events").and_then(Value::as_array_mut) {
+            steps.iter_mut().for_each(|step| self.apply(step));
+        }
+        let Some(locals) = event.get_mut("locals").and_then(Value::as_object_mut) else {
+            return;
+        };
+        let before = locals.len();
+        locals.retain(|name, _| self.keeps(name));
+        let hidden = before - locals.len();
+        if hidden > 0 {
+            event["locals_hidden"] = json!(hidden);
+        }
+    }
+}
+
+/// The config to trace `req` with: unchanged, or for `at_commit` pointed at a
+/// fresh worktree of that commit. The worktree is deleted when dropped, so
+/// keep it alive for as long as the tracer runs.
+fn checkout_for(req: &TraceRequest, config: &AppConfig) -> Result<(AppConfig, Option<Worktree>), FlowlensError> {
+    let Some(commit) = req.at_commit.as_deref() else {
+        return Ok((config.clone(), None));
+    };
+    let worktree = Worktree::create(&require_repo(config)?, commit)?;
+    let mut config = config.clone();
+    config.checkout_of = Some(config.repo());
+    config.repo_path = Some(worktree.path().to_path_buf());
+    Ok((config, Some(worktree)))
+}
+
+/// `get_tracer.py` invocation for `req`, without stdio set up. The interpreter
+/// is returned too so callers can tell whether it fell back to bare `python3`.
+fn trace_command(req: &TraceRequest, config: &AppConfig) -> Result<(Command, PythonInterpreter), FlowlensError> {
+    let repo = config.repo();
+    let python = config.checked_python()?;
+    let script_path = config.resolve_script(tools::TRACER)?;
+
+    let entry_kind = match req.entry_kind {
+        Some(kind) => kind,
+        None => EntryKind::detect(config, &req.entry_full_id)?,
+    };
+    println!("[Rust] Entry kind = {:?}", entry_kind);
+
+    let backend = config.tracer_backend(req.tracer_backend.as_deref())?;
+    let (stop_line, stop_at_entry) = req.initial_stop(config.default_stop);
+    let mut cmd = config.python_command(&python.path);
+    cmd.arg("-u")  // Unbuffered mode - critical for subprocess communication
+        .arg(script_path)
+        .arg("--repo_root")
+        .arg(&repo)
+        .arg("--entry_full_id")
+        .arg(&req.entry_full_id)
+        .arg("--args_json")
+        .arg(&req.args_json)
+        .arg("--stop_line")
+        .arg(stop_line.to_string())
+        .arg("--entry_kind")
+        .arg(entry_kind.as_arg())
+        .arg("--events-on")
+        .arg(config.events_on.as_arg())
+        .arg("--backend")
+        .arg(backend.as_arg())
+        .arg("--step_scope")
+        .arg(req.step_scope.as_arg())
+        .arg("--with-context")
+        .arg(config.context_lines().to_string())
+        .args(config.sys_path.iter().flat_map(|dir| ["--sys-path", dir.as_str()]));
+    if let Some(setup_expr) = &req.setup_expr {
+        cmd.arg("--setup_expr").arg(setup_expr);
+    }
+    if stop_at_entry {
+        cmd.arg("--stop_at_entry");
+    }
+    if let Some(seed) = req.random_seed {
+        cmd.arg("--seed").arg(seed.to_string());
+    }
+    if !req.breakpoints.is_empty() {
+        cmd.arg("--breakpoints").arg(breakpoints_arg(&repo, &req.breakpoints)?);
+    }
+    Ok((cmd, python))
 }
 
 impl Tracer {
-    fn spawn(req: &TraceRequest) -> Result<Self, String> {
-        let python = std::env::var("PYTHON_BIN").unwrap_or("python3".to_string());
-        let script_path = "../tools/get_tracer.py";
-
-        let mut child = Command::new(&python)
-            .arg("-u")  // Unbuffered mode - critical for subprocess communication
-            .arg(script_path)
-            .arg("--entry_full_id")
-            .arg(&req.entry_full_id)
-            .arg("--args_json")
-            .arg(&req.args_json)
-            .arg("--stop_line")
-            .arg(req.stop_line.to_string())
-            .stdin(Stdio::piped())
+    fn spawn(req: &TraceRequest, config: &AppConfig) -> Result<Self, FlowlensError> {
+        Self::launch(req, config, false)
+    }
+
+    /// Start Python for `entry_full_id` and let it import the entry module, but
+    /// not run anything until `start` sends the rest of the request.
+    fn prewarm(entry_full_id: &str, entry_kind: EntryKind, config: &AppConfig) -> Result<Self, FlowlensError> {
+        let req = TraceRequest {
+            entry_full_id: entry_full_id.to_string(),
+            entry_kind: Some(entry_kind),
+            ..Default::default()
+        };
+        Self::launch(&req, config, true)
+    }
+
+    fn launch(req: &TraceRequest, config: &AppConfig, prewarm: bool) -> Result<Self, FlowlensError> {
+        let (config, worktree) = checkout_for(req, config)?;
+        let config = &config;
+        let repo = require_repo(config)?;
+        let (mut cmd, python) = trace_command(req, config)?;
+        if prewarm {
+            cmd.arg("--prewarm");
+        }
+        let mut tracer = Self::from_command(cmd, req, config, worktree, prewarm)?;
+        tracer.python_warning = python.fallback.then(|| {
+            format!(
+                "no PYTHON_BIN or venv found in {}; tracing with {}",
+                repo.display(),
+                python.path.display()
+            )
+        });
+        Ok(tracer)
+    }
+
+    /// Spawn `cmd` as the tracer for `req` and start the threads around its pipes.
+    fn from_command(
+        mut cmd: Command,
+        req: &TraceRequest,
+        config: &AppConfig,
+        worktree: Option<Worktree>,
+        prewarm: bool,
+    ) -> Result<Self, FlowlensError> {
+        let repo = config.repo();
+        cmd.stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
-            .env("PYTHONUNBUFFERED", "1")  // Also set env var for extra safety
-            .spawn()
-            .map_err(|e| format!("Failed to spawn Python process: {}", e))?;
+            .env("PYTHONUNBUFFERED", "1"); // Also set env var for extra safety
+        let mut child = spawn_logged(&mut cmd).map_err(|e| python_spawn_error(&cmd, e))?;
+
+        let missing = |what: &str| FlowlensError::Tracer {
+            message: format!("Failed to capture Python {}", what),
+        };
+        let stdin = child.stdin.take().ok_or_else(|| missing("stdin"))?;
+        let stdout = child.stdout.take().ok_or_else(|| missing("stdout"))?;
+        let stderr = child.stderr.take().ok_or_else(|| missing("stderr"))?;
 
-        let stdin = child.stdin.take().ok_or("Failed to open Python stdin")?;
-        let stdout = child.stdout.take().ok_or("Failed to capture Python stdout")?;
-        let stderr = child.stderr.take().ok_or("Failed to capture Python stderr")?;
+        let (events, diagnostics): (Box<dyn BufRead + Send>, Box<dyn Read + Send>) = match config.events_on {
+            EventChannel::Stdout => (Box::new(BufReader::new(stdout)), Box::new(stderr)),
+            EventChannel::Stderr => (Box::new(BufReader::new(stderr)), Box::new(stdout)),
+        };
 
+        let (events, reader) = spawn_event_reader(events);
+        let log_tail = LogTail::default();
+        let (diagnostics, diagnostics_reader) = spawn_diagnostics_reader(diagnostics, config.tracer_log_lines(), log_tail.clone());
+
+        process::track_tracer(child.id());
         Ok(Self {
             child,
-            stdin,
-            stdout: BufReader::new(stdout),
-            stderr: BufReader::new(stderr),
+            stdin: spawn_stdin_writer(stdin),
+            events,
+            reader: Some(reader),
+            read_timeout: config.read_timeout(),
+            skip_steps: 0,
+            diagnostics,
+            diagnostics_reader: Some(diagnostics_reader),
+            events_on: config.events_on,
             // set current_flow to entry_full_id
-            current_flow: Some(req.entry_full_id.clone()),
+            current_flow: Some(normalize_entry_id(&req.entry_full_id)),
+            control_flow: ControlFlowTracker::default(),
+            python_warning: None,
+            at_commit: req.at_commit.clone(),
+            _worktree: worktree,
+            prewarmed: prewarm,
+            return_value: None,
+            paths: RepoPaths::new(&repo),
+            current_locals: None,
+            previous_locals: None,
+            current_line: None,
+            history: Vec::new(),
+            last_event: None,
+            last_raw_line: String::new(),
+            last_raw_len: 0,
+            raw_line_cap: config.payload_limits.max_payload_bytes,
+            log: VecDeque::new(),
+            log_cap: config.tracer_log_lines(),
+            log_tail,
+            local_filter: LocalFilter::default(),
+            breakpoints: req.breakpoints.clone(),
+            log_truncated: false,
         })
     }
-}
-
-// ------------------------
-// Tauri State Wrapper
-// ------------------------
-type SharedTracer = Mutex<Option<Tracer>>;
-
-// ------------------------
-// Trace Request Struct
-// ------------------------
-#[derive(Deserialize)]
-struct TraceRequest {
-    entry_full_id: String,
-    args_json: String,
-    stop_line: i32,
-}
 
+    fn keep_raw_line(&mut self, line: &str) {
+        let mut cut = line.len().min(self.raw_line_cap);
+        while !line.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        self.last_raw_line = line[..cut].to_string();
+        self.last_raw_len = line.len();
+    }
 
-// ------------------------
-// Main Tauri Command
-// ------------------------
-#[tauri::command]
-fn get_tracer_data(
-    req: TraceRequest,
-    tracer_state: State<SharedTracer>
-) -> Result<Value, String> {
-    use std::io::BufRead;
+    fn push_log(&mut self, line: String) {
+        if let Some(sink) = &*self.log_tail.lock().unwrap() {
+            sink("events", &line);
+        }
+        self.log.push_back(line);
+        while self.log.len() > self.log_cap {
+            self.log.pop_front();
+            self.log_truncated = true;
+        }
+    }
 
-    println!("[Rust] get_tracer_data called");
-    println!("[Rust] req.entry_full_id = {}", req.entry_full_id);
-    println!("[Rust] req.args_json = {}", req.args_json);
-    println!("[Rust] req.stop_line = {}", req.stop_line);
+    /// Hand a prewarmed tracer the request to run; it answers with its first event.
+    fn start(&mut self, req: &TraceRequest, default_stop: DefaultStop) -> Result<(), FlowlensError> {
+        let (stop_line, stop_at_entry) = req.initial_stop(default_stop);
+        let command = json!({
+            "cmd": "start",
+            "args_json": req.args_json,
+            "stop_line": stop_line,
+            "stop_at_entry": stop_at_entry,
+            "setup_expr": req.setup_expr,
+            "entry_kind": req.entry_kind.map(EntryKind::as_arg),
+            "seed": req.random_seed,
+            "step_scope": req.step_scope.as_arg(),
+        });
+        self.send_line(command.to_string())?;
+        self.prewarmed = false;
+        Ok(())
+    }
 
-    // Acquire lock
-    let mut tracer_guard = tracer_state.lock().unwrap();
-    println!("[Rust] tracer alive = {}", tracer_guard.is_some());
+    /// Whether the Python process is gone, e.g. after the entry function returned.
+    fn has_exited(&mut self) -> bool {
+        matches!(try_reap_tracer(&mut self.child), Ok(Some(_)))
+    }
 
-    let first_time = tracer_guard.is_none();
+    /// Kill the Python process and reap it. Harmless if it already exited.
+    /// Works while the traced code blocks (e.g. in `input()` or a socket read).
+    fn kill(&mut self) {
+        if let Ok(status) = kill_and_reap_tracer(&mut self.child) {
+            log_exit(self.child.id(), status);
+        }
+        self.join_readers();
+    }
 
-    // Spawn tracer if not alive
-    if first_time {
-        println!("[Rust] Spawning tracer…");
-        *tracer_guard = Some(Tracer::spawn(&req)?);
+    /// Wait (bounded) for the output readers to exit: the dead child's pipes give them EOF.
+    fn join_readers(&mut self) {
+        if let Some(reader) = self.reader.take() {
+            join_reader(reader, "event");
+        }
+        if let Some(reader) = self.diagnostics_reader.take() {
+            join_reader(reader, "diagnostics");
+        }
     }
 
-    // Check if we need to spawn a new tracer for a different function
-    let needs_new_tracer = if let Some(ref tracer) = *tracer_guard {
-        tracer.current_flow.as_deref() != Some(&req.entry_full_id)
-    } else {
-        false
-    };
+    /// Queue `line` for Python's stdin and wait (bounded) until it has been written.
+    fn send_line(&self, line: String) -> Result<(), FlowlensError> {
+        let timeout = FlowlensError::Timeout {
+            operation: "writing to tracer stdin".to_string(),
+            timeout_ms: STDIN_WRITE_TIMEOUT.as_millis() as u64,
+        };
+        let (done, written) = mpsc::channel();
 
-    // If new flow detected, kill old tracer and spawn new one
-    if needs_new_tracer {
-        println!("[Rust] New flow detected (old: {:?}, new: {}), spawning new tracer", 
-                 tracer_guard.as_ref().unwrap().current_flow, req.entry_full_id);
-        
-        // Kill the old tracer process
-        if let Some(ref mut old_tracer) = *tracer_guard {
-            let _ = old_tracer.child.kill(); // Ignore errors if already dead
-            let _ = old_tracer.child.wait(); // Wait for it to finish
+        match self.stdin.try_send(StdinCommand { line, done }) {
+            Ok(()) => {}
+            // Earlier writes are still stuck behind a Python process that isn't reading
+            Err(TrySendError::Full(_)) => return Err(timeout),
+            Err(TrySendError::Disconnected(_)) => {
+                return Err(FlowlensError::Tracer {
+                    message: "Python stdin is closed. The tracer process may have crashed.".to_string(),
+                })
+            }
         }
-        
-        // Spawn new tracer for the new function
-        *tracer_guard = Some(Tracer::spawn(&req)?);
-    }
 
-    let tracer = tracer_guard.as_mut().unwrap();
-    println!("[Rust] Current flow = {:?}", tracer.current_flow);
+        match written.recv_timeout(STDIN_WRITE_TIMEOUT) {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(e)) => Err(FlowlensError::Tracer {
+                message: format!("Failed to write to Python stdin: {}", e),
+            }),
+            Err(RecvTimeoutError::Timeout) => Err(timeout),
+            Err(RecvTimeoutError::Disconnected) => {
+                Err(FlowlensError::Tracer {
+                    message: "Python stdin writer stopped unexpectedly".to_string(),
+                })
+            }
+        }
+    }
 
-    // Determine if this is the first call for this tracer
-    // It's the first call if: this is the first time overall, OR we just spawned a new tracer
-    let is_first_call = first_time || needs_new_tracer;
+    /// Read every event Python emits for one step, up to its `step_end` marker,
+    /// so nothing is left buffered to be mistaken for the next step's answer.
+    /// The last event is returned; any before it (e.g. intermediate frames)
+    /// are attached to it as `intermediate_events`.
+    fn read_event(&mut self) -> Result<Value, FlowlensError> {
+        while self.skip_steps > 0 {
+            self.skip_step()?;
+        }
+        let mut events = Vec::new();
+        loop {
+            match self.read_event_line() {
+                Ok(event) if event.get("event").and_then(Value::as_str) == Some(STEP_END_EVENT) => break,
+                Ok(event) => events.push(event),
+                // The rest of this step may still come; skip it before the next read
+                Err(e @ FlowlensError::Timeout { .. }) => {
+                    self.skip_steps += 1;
+                    return Err(e);
+                }
+                // Python exits right after reporting a fatal error, without the marker
+                Err(e) if !events.is_empty() => {
+                    println!("[Rust] step ended without {} marker: {}", STEP_END_EVENT, e);
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
+        }
 
-    // Send continue command
-    if !is_first_call {
-        println!("[Rust] Sending continue_to {}", req.stop_line);
+        let Some(mut event) = events.pop() else {
+            return Err(FlowlensError::Tracer {
+                message: "Python ended a step without sending an event".to_string(),
+            });
+        };
+        if !events.is_empty() {
+            event["intermediate_events"] = Value::Array(events);
+        }
+        Ok(event)
+    }
 
-        writeln!(tracer.stdin, "{}", req.stop_line)
-        .map_err(|e| format!("Failed to write continue_to to Python stdin: {}", e))?;
+    /// Discard the events of a step whose read timed out, up to its marker.
+    fn skip_step(&mut self) -> Result<(), FlowlensError> {
+        loop {
+            let event = self.read_event_line()?;
+            if event.get("event").and_then(Value::as_str) == Some(STEP_END_EVENT) {
+                break;
+            }
+            println!("[Rust] skipping late event of a timed-out step");
+        }
+        self.skip_steps -= 1;
+        Ok(())
+    }
 
-        tracer.stdin.flush().map_err(|e| format!("Failed to flush stdin: {}", e))?;
-    } else {
-        println!("[Rust] First call for this function — Python will send initial event");
+    /// Wait up to `read_timeout` for the reader thread's next line.
+    fn recv_line(&self) -> Result<EventLine, FlowlensError> {
+        match self.events.recv_timeout(self.read_timeout) {
+            Ok(line) => Ok(line),
+            Err(RecvTimeoutError::Timeout) => Err(FlowlensError::Timeout {
+                operation: "waiting for the tracer's next event".to_string(),
+                timeout_ms: self.read_timeout.as_millis() as u64,
+            }),
+            // The reader stopped after passing on EOF or an error; keep reporting EOF
+            Err(RecvTimeoutError::Disconnected) => Ok(Ok(Vec::new())),
+        }
     }
 
-    // Read from stderr (Python writes events to stderr)
-    let mut line = String::new();
-    println!("[Rust] Reading event from Python stderr (stop_line={})...", req.stop_line);
+    /// Wait (up to `read_timeout`) for Python's next event line and parse it. Stray
+    /// non-JSON lines (e.g. prints from the traced code) are kept in `log` and
+    /// skipped; only tracebacks, EOF and read errors fail the read.
+    fn read_event_line(&mut self) -> Result<Value, FlowlensError> {
+        loop {
+            // Read one event line from whichever channel Python writes events to
+            let channel = self.events_on.as_arg();
+            println!("[Rust] Reading event from Python {}...", channel);
     
-    // Check if process is still alive before reading
-    if let Ok(Some(status)) = tracer.child.try_wait() {
-        return Err(format!("Python process exited with status: {:?} before reading event", status));
-    }
+            // Check if process is still alive before reading
+            if let Ok(Some(status)) = try_reap_tracer(&mut self.child) {
+                return Err(self.exited_error(status, "before reading event"));
+            }
+    
+            // Raw bytes, so one undecodable value (e.g. a binary repr) doesn't fail the step
+            let (bytes, read_result) = match self.recv_line()? {
+                Ok(bytes) => {
+                    let len = bytes.len();
+                    (bytes, Ok(len))
+                }
+                Err(e) => (Vec::new(), Err(e)),
+            };
+            let decoded = String::from_utf8_lossy(&bytes);
+            let encoding_issue = matches!(decoded, Cow::Owned(_));
+            let line = decoded.into_owned();
     
-    // NOTE: read_line() is blocking and will wait indefinitely for data.
-    // The Python script has a 30s timeout, but if it hangs before that,
-    // this will block forever. Consider using async I/O or a timeout mechanism.
-    // For now, we rely on Python's timeout to send an error event.
-    let read_result = tracer.stderr.read_line(&mut line);
+            // After reading, check if process died
+            if let Ok(Some(status)) = try_reap_tracer(&mut self.child) {
+                // Process died - check if we got any data before it died
+                if line.trim().is_empty() {
+                    return Err(self.exited_error(status, "before sending event. The process may have crashed or timed out."));
+                }
+                // If we got some data, continue processing it (might be a partial event)
+            }
     
-    // After reading, check if process died
-    if let Ok(Some(status)) = tracer.child.try_wait() {
-        // Process died - check if we got any data before it died
-        if line.trim().is_empty() {
-            return Err(format!("Python process exited with status: {:?} before sending event. The process may have crashed or timed out.", status));
+            // Read one line - Python should send JSON on a single line
+            match read_result {
+                Ok(0) => {
+                    // EOF - process might have closed the event channel
+                    if let Ok(Some(status)) = try_reap_tracer(&mut self.child) {
+                        return Err(self.exited_error(status, &format!("before sending event. {} was closed.", channel)));
+                    }
+                    return Err(FlowlensError::Tracer {
+                        message: format!("Python {} closed unexpectedly (EOF). The tracer process may have crashed.", channel),
+                    });
+                }
+                Ok(_) => {
+                    // Successfully read a line
+                }
+                Err(e) => {
+                    // Check if process died
+                    if let Ok(Some(status)) = try_reap_tracer(&mut self.child) {
+                        return Err(self.exited_error(status, &format!("while reading {}. Error: {}. The process may have crashed.", channel, e)));
+                    }
+                    return Err(FlowlensError::Tracer {
+                        message: format!("Failed to read Python {}: {}. The tracer may be unresponsive.", channel, e),
+                    });
+                }
+            }
+
+            let line = line.trim();
+            println!(
+                "[Rust] Received from Python (len={}): {}",
+                line.len(),
+                if line.len() > 200 {
+                    format!("{}...", line.chars().take(200).collect::<String>())
+                } else {
+                    line.to_string()
+                }
+            );
+
+            if line.is_empty() {
+                continue;
+            }
+
+            // Try to parse as JSON
+            let mut event_json: Value = match serde_json::from_str(line) {
+                Ok(event_json) => event_json,
+                // If parsing fails, check if it's an error message. Only stderr
+                // carries Python's own errors; on stdout this is the traced code printing
+                Err(_)
+                    if self.events_on == EventChannel::Stderr
+                        && (line.starts_with("Exception") || line.starts_with("Traceback") || line.starts_with("Error:")) =>
+                {
+                    self.keep_raw_line(line);
+                    return Err(FlowlensError::InvalidJson {
+                        message: format!("Python sent error output instead of JSON:\n{}", line),
+                    });
+                }
+                Err(e) => {
+                    println!("[Rust] Skipping non-JSON line from Python: {}", e);
+                    self.keep_raw_line(line);
+                    self.push_log(line.to_string());
+                    continue;
+                }
+            };
+
+            // Every step ends with the marker, which would hide the line worth looking at
+            if event_json.get("event").and_then(Value::as_str) != Some(STEP_END_EVENT) {
+                self.keep_raw_line(line);
+            }
+            if encoding_issue {
+                println!("[Rust] Event line was not valid UTF-8; invalid bytes were replaced");
+                if let Value::Object(fields) = &mut event_json {
+                    fields.insert("encoding_issue".to_string(), json!(true));
+                }
+            }
+            println!("[Rust] Parsed event JSON = {}", event_json);
+            return Ok(event_json);
         }
-        // If we got some data, continue processing it (might be a partial event)
     }
-    
-    // Read one line - Python should send JSON on a single line
-    match read_result {
-        Ok(0) => {
-            // EOF - process might have closed stderr
-            if let Ok(Some(status)) = tracer.child.try_wait() {
-                return Err(format!("Python process exited with status: {:?} before sending event. stderr was closed.", status));
+
+    /// Read the event produced by advancing execution, tagged with its
+    /// control-flow kind and fitted to the payload budget.
+    fn read_step_event(&mut self, limits: &PayloadLimits) -> Result<Value, FlowlensError> {
+        let started = Instant::now();
+        let mut event = self.read_event()?;
+        let step_ms = started.elapsed().as_millis() as u64;
+        let intermediate = event.get("intermediate_events").and_then(Value::as_array).into_iter().flatten();
+        let count = intermediate.clone().count();
+        for (i, step) in intermediate.chain(std::iter::once(&event)).enumerate() {
+            if let Some(phase) = Phase::of(step)? {
+                let filename = step.get("filename").and_then(Value::as_str).unwrap_or_default();
+                self.history.push(Step {
+                    function: step.get("function").and_then(Value::as_str).unwrap_or_default().to_string(),
+                    line: step.get("line").and_then(Value::as_u64).unwrap_or(0),
+                    file: self.paths.relative(filename).unwrap_or_else(|| filename.to_string()),
+                    phase,
+                    step_ms: if i == count { step_ms } else { 0 },
+                });
             }
-            return Err("Python stderr closed unexpectedly (EOF). The tracer process may have crashed.".to_string());
         }
-        Ok(_) => {
-            // Successfully read a line
+        if Phase::of(&event)?.is_some() {
+            let tag = self.control_flow.classify(&event);
+            event["control_flow"] = json!(tag);
+            // Every step event says whether it ran in a method, null when not
+            let receiver = Receiver::of(&event)?;
+            event["receiver"] = json!(receiver);
         }
-        Err(e) => {
-            // Check if process died
-            if let Ok(Some(status)) = tracer.child.try_wait() {
-                return Err(format!("Python process exited with status: {:?} while reading stderr. Error: {}. The process may have crashed.", status, e));
-            }
-            return Err(format!("Failed to read Python stderr: {}. The tracer may be unresponsive.", e));
+        if event.get("completed").and_then(Value::as_bool) == Some(true) {
+            self.return_value = Some(event.get("return_value").cloned().unwrap_or(Value::Null));
+        }
+        if let Some(locals) = event.get("locals").and_then(Value::as_object) {
+            self.previous_locals = self.current_locals.replace(locals.clone());
+            self.current_line = event.get("line").and_then(Value::as_u64);
+        }
+        if let Some(warning) = self.python_warning.take() {
+            event["python_warning"] = json!(warning);
         }
+        self.local_filter.apply(&mut event);
+        self.paths.relativize(&mut event);
+        enforce_limits(&mut event, limits);
+        self.last_event = Some(event.clone());
+        Ok(event)
     }
 
-let line = line.trim();
-println!(
-    "[Rust] Received from Python (len={}): {}",
-    line.len(),
-    if line.len() > 200 {
-        format!("{}...", &line[..200])
-    } else {
-        line.to_string()
-    }
-);
+    /// Error for a tracer that exited, with the newest lines it wrote on the
+    /// diagnostic channel. Waits for that channel's EOF, so only call this once
+    /// the process is gone.
+    fn exited_error(&mut self, status: ExitStatus, context: &str) -> FlowlensError {
+        log_exit(self.child.id(), status);
+        if let Some(reader) = self.diagnostics_reader.take() {
+            join_reader(reader, "diagnostics");
+        }
+        let output = Vec::from(self.diagnostics.lock().unwrap().clone()).join("\n");
 
-    if line.is_empty() {
-        return Err("Empty response from Python".to_string());
+        FlowlensError::PythonExited {
+            code: status.code(),
+            signal: exit_signal(status),
+            message: format!("Python process {} {}", describe_exit(status), context),
+            output: output.trim().to_string(),
+        }
     }
+}
 
-    // Try to parse as JSON
-    let event_json: Value = serde_json::from_str(&line)
-        .map_err(|e| {
-            // If parsing fails, check if it's an error message
-            if line.starts_with("Exception") || line.starts_with("Traceback") || line.starts_with("Error:") {
-                format!("Python sent error output instead of JSON:\n{}", line)
-            } else {
-                format!(
-                    "Failed to parse JSON from Python: {} -- received: {}",
-                    e,
-                    if line.len() > 500 {
-                        format!("{}...", &line[..500])
-                    } else {
-                        line.to_string()
-                    }
-                )
-            }
-        })?;
-
-    println!("[Rust] Parsed event JSON = {}", event_json);
-    Ok(event_json)    
+/// However a tracer goes away (replaced, stopped, app exit), its Python
+/// process goes with it.
+impl Drop for Tracer {
+    fn drop(&mut self) {
+        match try_reap_tracer(&mut self.child) {
+            Ok(None) => self.kill(),
+            // Already exited or killed and reaped; only the reader may be left
+            _ => self.join_readers(),
+        }
+    }
 }
 
+/// Canonical form of an entry id (`/path/to/file.py::function`) so that ids
+/// differing only in whitespace, path separators or a leading slash compare equal.
+fn normalize_entry_id(entry_full_id: &str) -> String {
+    let (path, function) = match entry_full_id.split_once("::") {
+        Some((path, function)) => (path, Some(function)),
+        None => (entry_full_id, None),
+    };
 
+    let segments: Vec<&str> = path
+        .trim()
+        .split(['/', '\\'])
+        .filter(|s| !s.is_empty() && *s != ".")
+        .collect();
+    let path = format!("/{}", segments.join("/"));
 
+    match function {
+        Some(function) => format!("{}::{}", path, function.trim()),
+        None => path,
+    }
+}
 
-#[tauri::command]
-fn get_function_signature(entry_full_id: String) -> Result<Value, String> {
-    println!("[Rust] get_function_signature called with entry_full_id = {}", entry_full_id);
-    
-    let repo = "/home/bimal/Documents/ucsd/research/code/trap";
-    let python = std::env::var("PYTHON_BIN").unwrap_or("python3".to_string());
-    let script_path = "../tools/get_tracer.py";
-    
-    let output = Command::new(&python)
-        .arg("-u")
-        .arg(script_path)
-        .arg("--repo_root")
-        .arg(&repo)
-        .arg("--entry_full_id")
-        .arg(&entry_full_id)
-        .arg("--get_signature")
-        .output()
-        .map_err(|e| format!("Failed to run Python script: {}", e))?;
-    
-    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-    
-    if !output.status.success() {
-        return Err(format!("Python script error: {}", stdout));
+/// Key of a tracer in `SharedTracer`: the normalized entry id, plus
+/// `@<commit>` for an `at_commit` trace, so a flow can be traced at HEAD and
+/// at older commits side by side. Commands that take an `entry_full_id`
+/// accept this form to reach a commit's tracer.
+fn tracer_key(entry_full_id: &str, at_commit: Option<&str>) -> String {
+    let flow = normalize_entry_id(entry_full_id);
+    match at_commit.map(str::trim).filter(|commit| !commit.is_empty()) {
+        Some(commit) => format!("{}@{}", flow, commit),
+        None => flow,
     }
-    
-    let signature: Value = serde_json::from_str(&stdout)
-        .map_err(|e| format!("Failed to parse signature JSON: {} -- received: {}", e, stdout))?;
-    
-    Ok(signature)
 }
 
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    println!("[flowlens] run: starting tauri builder");
-    tauri::Builder::default()
-        .manage(Mutex::new(None::<Tracer>))  // register the shared tracer state
+// ------------------------
+// Tauri State Wrapper
+// ------------------------
+/// Live tracers by `tracer_key`, at most `max_tracers` of them. The map is
+/// only locked to look tracers up, add or remove them; each tracer has its own
+/// lock for the commands using it, so a slow step in one flow never holds up
+/// the others.
+#[derive(Default)]
+struct SharedTracer {
+    map: Mutex<HashMap<String, TracerEntry>>,
+    /// Notified whenever a `Spawning` entry is resolved
+    spawned: Condvar,
+}
+
+enum TracerEntry {
+    /// Held while a tracer spawns with the map unlocked, which can take
+    /// seconds (Python's imports, `git worktree add` for `at_commit`). It
+    /// counts toward `max_tracers`; lookups of its key wait on `spawned`.
+    Spawning,
+    Ready(Arc<TracerSlot>),
+}
+
+impl TracerEntry {
+    fn ready(&self) -> Option<&Arc<TracerSlot>> {
+        match self {
+            Self::Ready(slot) => Some(slot),
+            Self::Spawning => None,
+        }
+    }
+}
+
+/// A tracer in `SharedTracer`, with what eviction needs kept beside it so
+/// that choosing a tracer to evict never waits on a busy one.
+struct TracerSlot {
+    /// Held for as long as a command uses the tracer, which may be a whole step
+    tracer: Mutex<Tracer>,
+    /// Set by `pin_tracer`; a pinned tracer is never evicted to make room for another flow
+    pinned: AtomicBool,
+    /// When a command last used this tracer; the oldest unpinned one is evicted first
+    last_used: Mutex<Instant>,
+    /// Of the Python process, for `find_orphans`
+    pid: u32,
+    /// First and last line of the entry function, once looked up for
+    /// `check_stop_line`. Out of `tracer` so the repo watcher can drop it
+    /// without waiting on a step
+    source_span: Mutex<Option<(u32, u32)>>,
+}
+
+impl TracerSlot {
+    fn new(tracer: Tracer) -> Arc<Self> {
+        Arc::new(Self {
+            pid: tracer.child.id(),
+            tracer: Mutex::new(tracer),
+            pinned: AtomicBool::new(false),
+            last_used: Mutex::new(Instant::now()),
+            source_span: Mutex::new(None),
+        })
+    }
+
+    fn touch(&self) {
+        *self.last_used.lock().unwrap() = Instant::now();
+    }
+
+    fn last_used(&self) -> Instant {
+        *self.last_used.lock().unwrap()
+    }
+
+    /// Lock the tracer for a command on `entry_full_id`, waiting for the
+    /// command using it to finish. A prewarmed tracer doesn't count until it
+    /// has been started.
+    fn lock_active(&self, entry_full_id: &str) -> Result<MutexGuard<'_, Tracer>, FlowlensError> {
+        let tracer = self.tracer.lock().unwrap();
+        if tracer.prewarmed {
+            return Err(FlowlensError::NoActiveTracer {
+                entry_full_id: entry_full_id.to_string(),
+            });
+        }
+        Ok(tracer)
+    }
+}
+
+/// The tracer under `key`, waiting for it first if it is still spawning.
+/// The map is unlocked again on return.
+fn tracer_slot(tracers: &SharedTracer, key: &str) -> Option<Arc<TracerSlot>> {
+    let mut tracer_guard = tracers.map.lock().unwrap();
+    while let Some(TracerEntry::Spawning) = tracer_guard.get(key) {
+        tracer_guard = tracers.spawned.wait(tracer_guard).unwrap();
+    }
+    tracer_guard.get(key).and_then(TracerEntry::ready).cloned()
+}
+
+/// Every tracer with its key, copied out so none has to be locked while the
+/// map is. Ones still spawning are left out.
+fn tracer_slots(tracers: &SharedTracer) -> Vec<(String, Arc<TracerSlot>)> {
+    let tracer_guard = tracers.map.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    tracer_guard
+        .iter()
+        .filter_map(|(key, entry)| Some((key.clone(), entry.ready()?.clone())))
+        .collect()
+}
+
+/// Remove `slot` from under `key`, unless another tracer replaced it meanwhile.
+fn remove_slot(tracers: &SharedTracer, key: &str, slot: &Arc<TracerSlot>) {
+    let mut tracer_guard = tracers.map.lock().unwrap();
+    if tracer_guard.get(key).and_then(TracerEntry::ready).is_some_and(|current| Arc::ptr_eq(current, slot)) {
+        tracer_guard.remove(key);
+    }
+}
+
+/// Take every tracer out of the map, e.g. to stop them all. Spawns still
+/// running find their entry gone and stop the tracer they started.
+fn take_all_tracers(tracers: &SharedTracer) -> Vec<Arc<TracerSlot>> {
+    let mut tracer_guard = tracers.map.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let slots = tracer_guard
+        .drain()
+        .filter_map(|(_, entry)| match entry {
+            TracerEntry::Ready(slot) => Some(slot),
+            TracerEntry::Spawning => None,
+        })
+        .collect();
+    drop(tracer_guard);
+    tracers.spawned.notify_all();
+    slots
+}
+
+/// Add a `Spawning` entry for `key`, first making room for it (see
+/// `make_room`). Resolve it with `settle_spawn` once the spawn is done.
+fn claim_spawn(tracer_guard: &mut HashMap<String, TracerEntry>, key: &str, max: usize) -> Result<Vec<Arc<TracerSlot>>, FlowlensError> {
+    let evicted = make_room(tracer_guard, max)?;
+    tracer_guard.insert(key.to_string(), TracerEntry::Spawning);
+    Ok(evicted)
+}
+
+/// Resolve the `Spawning` entry under `key` to `slot`, or remove it when the
+/// spawn failed, and wake the lookups waiting on it. Fails when the entry was
+/// taken out meanwhile (`close_tracer`, `emergency_stop`, `shutdown`); the
+/// caller then drops `slot`, which stops it.
+fn settle_spawn(tracers: &SharedTracer, key: &str, slot: Option<&Arc<TracerSlot>>) -> Result<(), FlowlensError> {
+    let mut tracer_guard = tracers.map.lock().unwrap();
+    let claimed = matches!(tracer_guard.get(key), Some(TracerEntry::Spawning));
+    if claimed {
+        match slot {
+            Some(slot) => tracer_guard.insert(key.to_string(), TracerEntry::Ready(slot.clone())),
+            None => tracer_guard.remove(key),
+        };
+    }
+    drop(tracer_guard);
+    tracers.spawned.notify_all();
+    if claimed || slot.is_none() {
+        return Ok(());
+    }
+    Err(FlowlensError::Tracer {
+        message: format!("the tracer for {} was stopped while it was starting", key),
+    })
+}
+
+/// Take the least recently used unpinned tracers out until at most `cap` are
+/// left, or only pinned ones. Returns those taken out; dropping them stops
+/// their processes, so do that with the map unlocked.
+fn evict_lru(tracers: &mut HashMap<String, TracerEntry>, cap: usize) -> Vec<Arc<TracerSlot>> {
+    let mut evicted = Vec::new();
+    while tracers.len() > cap {
+        let oldest = tracers
+            .iter()
+            .filter_map(|(flow, entry)| Some((flow, entry.ready()?)))
+            .filter(|(_, slot)| !slot.pinned.load(Ordering::SeqCst))
+            .min_by_key(|(_, slot)| slot.last_used())
+            .map(|(flow, _)| flow.clone());
+        let Some(flow) = oldest else {
+            break;
+        };
+        println!("[flowlens] evicting least recently used tracer {}", flow);
+        if let Some(TracerEntry::Ready(slot)) = tracers.remove(&flow) {
+            evicted.push(slot);
+        }
+    }
+    evicted
+}
+
+/// Make room for one more tracer under `max`, evicting as needed (see
+/// `evict_lru`). Fails when the tracers that would have to go are all pinned
+/// or still spawning.
+fn make_room(tracers: &mut HashMap<String, TracerEntry>, max: usize) -> Result<Vec<Arc<TracerSlot>>, FlowlensError> {
+    let evicted = evict_lru(tracers, max.saturating_sub(1));
+    if tracers.len() < max {
+        return Ok(evicted);
+    }
+    Err(FlowlensError::TracerPinned {
+        pinned_entry_full_id: tracers.keys().min().cloned().unwrap_or_default(),
+    })
+}
+
+// ------------------------
+// Trace Request Struct
+// ------------------------
+#[derive(Serialize, Deserialize, Clone, Default)]
+struct TraceRequest {
+    entry_full_id: String,
+    #[serde(default)]
+    args_json: String,
+    /// Line to run to. Omitted (or 0) on the first request means: pause where
+    /// `default_stop` says, unless there are breakpoints.
+    #[serde(default)]
+    stop_line: i32,
+    /// How the tracer should drive the entry. Detected from the signature when omitted.
+    #[serde(default)]
+    entry_kind: Option<EntryKind>,
+    /// Python expression evaluated in the entry module to build the call
+    /// arguments, for inputs that can't be written as JSON (e.g. `make_sample_request()`)
+    #[serde(default)]
+    setup_expr: Option<String>,
+    /// Trace the code as of this commit, checked out in a temporary git worktree
+    #[serde(default)]
+    at_commit: Option<String>,
+    /// Trace even if `stop_line` is outside the entry function; the event then
+    /// carries a `stop_line_warning` instead of the request failing
+    #[serde(default)]
+    allow_out_of_range: bool,
+    /// Answer a continue with only what changed since the previous event (see
+    /// `delta::encode`); `refresh_current_event` still returns the full event
+    #[serde(default)]
+    delta: bool,
+    /// Extra places to pause, in any repo file. Applied when the tracer is spawned.
+    #[serde(default)]
+    breakpoints: Vec<Breakpoint>,
+    /// Seed for `random` (and `numpy.random` if the code uses it), set before
+    /// the arguments are built. Best-effort: time, hashing, threads and other
+    /// generators are not controlled.
+    #[serde(default)]
+    random_seed: Option<u64>,
+    /// Opaque id from the UI, logged with the request and echoed as the
+    /// event's `request_id`, to match a user action to its logs and events
+    #[serde(default)]
+    request_id: Option<String>,
+    /// Replace a running tracer for the same flow with a fresh one, from the top
+    #[serde(default)]
+    restart: bool,
+    /// One of `available_backends`; the configured backend when omitted
+    #[serde(default)]
+    tracer_backend: Option<String>,
+    /// Which frames the tracer follows; library frames are skipped by default
+    #[serde(default)]
+    step_scope: StepScope,
+    /// How long this request waits for its event, in ms; the configured read
+    /// timeout when omitted. A timeout kills the tracer, so the next request
+    /// starts a fresh one.
+    #[serde(default)]
+    timeout_ms: Option<u64>,
+}
+
+/// A line to pause at, anywhere in the repo. Events stopped here carry a
+/// matching `breakpoint: {file, line}`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Breakpoint {
+    /// Repo-relative path, e.g. `backend/services/util.py`
+    file: String,
+    line: u32,
+}
+
+/// `--breakpoints` value (`file:line,...`) for `breakpoints`, rejecting files
+/// that don't exist or resolve outside `repo`.
+fn breakpoints_arg(repo: &Path, breakpoints: &[Breakpoint]) -> Result<String, FlowlensError> {
+    let invalid = |message: String| FlowlensError::InvalidRequest { message };
+    let root = repo.canonicalize().map_err(|_| FlowlensError::RepoNotConfigured {
+        path: repo.display().to_string(),
+    })?;
+    let mut spec = Vec::new();
+    for bp in breakpoints {
+        let path = root
+            .join(bp.file.trim_start_matches('/'))
+            .canonicalize()
+            .map_err(|e| invalid(format!("breakpoint file {} not found: {}", bp.file, e)))?;
+        let Ok(relative) = path.strip_prefix(&root) else {
+            return Err(invalid(format!("breakpoint file {} is outside the repo", bp.file)));
+        };
+        if bp.line == 0 {
+            return Err(invalid(format!("breakpoint line in {} must be at least 1", bp.file)));
+        }
+        spec.push(format!("{}:{}", relative.display(), bp.line));
+    }
+    Ok(spec.join(","))
+}
+
+/// Save `breakpoints` under `name` in the config dir, replacing a preset of
+/// the same name, so the layout can be reused or shared with the team.
+#[tauri::command]
+fn save_breakpoint_preset(
+    name: String,
+    breakpoints: Vec<Breakpoint>,
+    presets: State<SharedBreakpointPresets>,
+) -> Result<(), FlowlensError> {
+    println!("[flowlens] save_breakpoint_preset {} ({} breakpoints)", name, breakpoints.len());
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(FlowlensError::InvalidRequest {
+            message: "preset name is empty".to_string(),
+        });
+    }
+    presets
+        .lock()
+        .unwrap()
+        .save_preset(name, breakpoints)
+}
+
+#[tauri::command]
+fn list_breakpoint_presets(presets: State<SharedBreakpointPresets>) -> Vec<BreakpointPreset> {
+    presets.lock().unwrap().list()
+}
+
+/// Breakpoints of preset `name`, checked against the current repo, to pass as
+/// `breakpoints` in the `get_tracer_data` request that starts tracing `entry_full_id`.
+#[tauri::command]
+fn apply_breakpoint_preset(
+    entry_full_id: String,
+    name: String,
+    config: State<SharedConfig>,
+    presets: State<SharedBreakpointPresets>,
+) -> Result<Vec<Breakpoint>, FlowlensError> {
+    println!("[flowlens] apply_breakpoint_preset {} to {}", name, entry_full_id);
+    let breakpoints = presets
+        .lock()
+        .unwrap()
+        .get(name.trim())
+        .map(<[Breakpoint]>::to_vec)
+        .ok_or_else(|| FlowlensError::InvalidRequest {
+            message: format!("no breakpoint preset named {:?}", name),
+        })?;
+    let repo = config.lock().unwrap().repo();
+    breakpoints_arg(&repo, &breakpoints)?;
+    Ok(breakpoints)
+}
+
+impl TraceRequest {
+    /// `tracer_key` of the tracer this request steps.
+    fn key(&self) -> String {
+        tracer_key(&self.entry_full_id, self.at_commit.as_deref())
+    }
+
+    /// `(stop_line, stop_at_entry)` for a newly spawned tracer.
+    fn initial_stop(&self, default_stop: DefaultStop) -> (i32, bool) {
+        if self.stop_line > 0 || !self.breakpoints.is_empty() {
+            return (self.stop_line, false);
+        }
+        match default_stop {
+            // Every executed line is at or past line 1
+            DefaultStop::FirstLine => (1, false),
+            DefaultStop::EntryOnly => (0, true),
+            // Never reached, so only the return is reported
+            DefaultStop::NoStop => (i32::MAX, false),
+        }
+    }
+
+    /// Arguments come either from `args_json` or from `setup_expr`, never both.
+    fn validate(&self) -> Result<(), FlowlensError> {
+        let has_setup = self.setup_expr.as_deref().is_some_and(|e| !e.trim().is_empty());
+
+        let args: Value = if self.args_json.trim().is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_str(&self.args_json).map_err(|e| FlowlensError::InvalidRequest {
+                message: format!("args_json is not valid JSON: {}", e),
+            })?
+        };
+        let non_empty = |key: &str| match args.get(key) {
+            Some(Value::Array(items)) => !items.is_empty(),
+            Some(Value::Object(items)) => !items.is_empty(),
+            Some(Value::Null) | None => false,
+            Some(_) => true,
+        };
+        let has_args = non_empty("args") || non_empty("kwargs");
+
+        if has_setup && has_args {
+            return Err(FlowlensError::InvalidRequest {
+                message: "pass either setup_expr or args_json arguments, not both".to_string(),
+            });
+        }
+        if let Some(commit) = self.at_commit.as_deref() {
+            if commit.trim().is_empty() || commit.starts_with('-') {
+                return Err(FlowlensError::InvalidRequest {
+                    message: format!("invalid at_commit: {:?}", commit),
+                });
+            }
+        }
+        if self.timeout_ms == Some(0) {
+            return Err(FlowlensError::InvalidRequest {
+                message: "timeout_ms must be greater than zero".to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Generators have to be iterated and coroutines need an event loop, so the
+/// tracer can't just call them like a plain function.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+enum EntryKind {
+    Function,
+    Generator,
+    Async,
+}
+
+/// Which frames the tracer instruments. Library code (the stdlib, and
+/// site-packages even inside the repo) is usually noise when stepping.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+enum StepScope {
+    /// Only frames of repo files; `run_to` and `finish` never stop in library code
+    #[default]
+    Project,
+    /// Every frame, as plain `bdb` does
+    All,
+}
+
+impl StepScope {
+    fn as_arg(self) -> &'static str {
+        match self {
+            StepScope::Project => "project",
+            StepScope::All => "all",
+        }
+    }
+}
+
+impl EntryKind {
+    fn as_arg(self) -> &'static str {
+        match self {
+            EntryKind::Function => "function",
+            EntryKind::Generator => "generator",
+            EntryKind::Async => "async",
+        }
+    }
+
+    /// Look up the entry's signature and read its kind.
+    fn detect(config: &AppConfig, entry_full_id: &str) -> Result<Self, FlowlensError> {
+        Self::of(run_signature(config, entry_full_id, &[])?, entry_full_id)
+    }
+
+    /// Read the `kind` reported by `--get_signature`.
+    fn of(signature: FunctionSignature, entry_full_id: &str) -> Result<Self, FlowlensError> {
+        if let Some(err) = signature.error {
+            return Err(FlowlensError::InvalidRequest {
+                message: format!("Could not inspect {}: {}", entry_full_id, err),
+            });
+        }
+        match signature.kind.as_deref() {
+            None | Some("function") => Ok(EntryKind::Function),
+            Some("generator") => Ok(EntryKind::Generator),
+            Some("async") | Some("async_generator") => Ok(EntryKind::Async),
+            Some(other) => Err(FlowlensError::InvalidRequest {
+                message: format!("unsupported entry kind '{}' for {}", other, entry_full_id),
+            }),
+        }
+    }
+}
+
+
+// ------------------------
+// Main Tauri Command
+// ------------------------
+/// Why `get_tracer_data` started a trace from the top, reported in its
+/// `spawn_info` so the UI knows the stepping state was reset.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+enum SpawnReason {
+    /// No tracer was running for this flow, or only a prewarmed one
+    FirstRun,
+    /// The tracer for this flow had exited, e.g. after its function returned
+    PreviousDied,
+    /// The request set `restart`
+    RestartRequested,
+}
+
+/// Run `f` on the tracer for `req`'s flow and commit, spawning one with
+/// `spawn` first (and evicting the least recently used if at `max_tracers`)
+/// when there is none or it can't continue. Tracers for other flows or commits
+/// are left alone. `f` is told why the trace starts over, or `None` when the
+/// running tracer continues. As in `with_active_tracer`, a tracer whose read
+/// timed out is killed.
+///
+/// The spawn runs with the map unlocked behind a `Spawning` entry, so other
+/// calls for the flow wait for it instead of spawning their own. `f` runs
+/// under the tracer's own lock, taken before anyone else can see a fresh
+/// tracer, so another command can't slip in before its first event.
+fn ensure_tracer<T>(
+    tracers: &SharedTracer,
+    req: &TraceRequest,
+    config: &AppConfig,
+    spawn: impl Fn(&TraceRequest, &AppConfig) -> Result<Tracer, FlowlensError>,
+    f: impl FnOnce(&mut Tracer, Option<SpawnReason>) -> Result<T, FlowlensError>,
+) -> Result<T, FlowlensError> {
+    let flow = req.key();
+    let mut reason = SpawnReason::FirstRun;
+    loop {
+        let existing = tracer_slot(tracers, &flow);
+        if let Some(slot) = &existing {
+            slot.touch();
+            let mut tracer = slot.tracer.lock().unwrap();
+            let exited = tracer.has_exited();
+            if !tracer.prewarmed && !exited && !req.restart {
+                return run_on_slot(tracers, &flow, slot, tracer, |tracer| f(tracer, None));
+            }
+            // Breakpoints are command-line arguments, so only a request without
+            // them can reuse a prewarmed process
+            if tracer.prewarmed && !exited && req.breakpoints.is_empty() {
+                // Python sends the first event as soon as it gets the request
+                println!("[Rust] Starting prewarmed tracer for {}", flow);
+                tracer.start(req, config.default_stop)?;
+                return run_on_slot(tracers, &flow, slot, tracer, |tracer| f(tracer, Some(SpawnReason::FirstRun)));
+            }
+            if exited {
+                reason = SpawnReason::PreviousDied;
+            } else if req.restart {
+                reason = SpawnReason::RestartRequested;
+            }
+        }
+
+        let mut tracer_guard = tracers.map.lock().unwrap();
+        // Another call may have replaced it, or started to, since the lookup
+        let current = match tracer_guard.get(&flow) {
+            Some(TracerEntry::Spawning) => continue,
+            Some(TracerEntry::Ready(slot)) => Some(Arc::as_ptr(slot)),
+            None => None,
+        };
+        if current != existing.as_ref().map(Arc::as_ptr) {
+            continue;
+        }
+        if existing.is_some() {
+            println!("[Rust] Respawning tracer for {} ({:?})", flow, reason);
+        }
+        // Removed first so a failed spawn doesn't leave the dead tracer behind
+        let replaced = tracer_guard.remove(&flow);
+        let claimed = claim_spawn(&mut tracer_guard, &flow, config.max_tracers());
+        drop(tracer_guard);
+        drop((replaced, claimed?));
+
+        println!("[Rust] Spawning tracer…");
+        let slot = match spawn(req, config) {
+            Ok(tracer) => TracerSlot::new(tracer),
+            Err(e) => {
+                settle_spawn(tracers, &flow, None)?;
+                return Err(e);
+            }
+        };
+        let tracer = slot.tracer.lock().unwrap();
+        settle_spawn(tracers, &flow, Some(&slot))?;
+        return run_on_slot(tracers, &flow, &slot, tracer, |tracer| f(tracer, Some(reason)));
+    }
+}
+
+/// Run `f` on the locked `tracer` of `slot`, the one under `key`. When it
+/// times out the tracer is likely hung, so it is killed rather than left to
+/// answer the next request late; the next trace request starts over.
+fn run_on_slot<T>(
+    tracers: &SharedTracer,
+    key: &str,
+    slot: &Arc<TracerSlot>,
+    mut tracer: MutexGuard<'_, Tracer>,
+    f: impl FnOnce(&mut Tracer) -> Result<T, FlowlensError>,
+) -> Result<T, FlowlensError> {
+    let result = f(&mut tracer);
+    if let Err(e @ FlowlensError::Timeout { .. }) = &result {
+        println!("[flowlens] tracer timed out, killing it: {}", e);
+        tracer.kill();
+        drop(tracer);
+        remove_slot(tracers, key, slot);
+    }
+    result
+}
+
+/// Spawn the tracer for `entry_full_id` as soon as the user selects a function,
+/// so the module import is done by the time they trace. The next
+/// `get_tracer_data` for this flow supplies the arguments and gets its first
+/// event without the spawn latency. A prewarmed tracer counts toward
+/// `max_tracers` like any other.
+#[tauri::command]
+fn prewarm_tracer(
+    entry_full_id: String,
+    tracer_state: State<SharedTracer>,
+    config: State<SharedConfig>,
+) -> Result<Value, FlowlensError> {
+    println!("[Rust] prewarm_tracer {}", entry_full_id);
+    let config = config.lock().unwrap().clone();
+    let flow = normalize_entry_id(&entry_full_id);
+
+    if tracer_state.map.lock().unwrap().contains_key(&flow) {
+        return Ok(json!({ "entry_full_id": flow, "spawned": false }));
+    }
+    // Runs Python, so not under the tracer lock
+    let entry_kind = EntryKind::detect(&config, &entry_full_id)?;
+
+    let mut tracer_guard = tracer_state.map.lock().unwrap();
+    // Another request may have started one meanwhile
+    if tracer_guard.contains_key(&flow) {
+        return Ok(json!({ "entry_full_id": flow, "spawned": false }));
+    }
+    let claimed = claim_spawn(&mut tracer_guard, &flow, config.max_tracers());
+    drop(tracer_guard);
+    drop(claimed?);
+
+    let slot = Tracer::prewarm(&entry_full_id, entry_kind, &config).map(TracerSlot::new);
+    settle_spawn(&tracer_state, &flow, slot.as_ref().ok())?;
+    slot?;
+    Ok(json!({ "entry_full_id": flow, "spawned": true }))
+}
+
+/// Look up what `req` needs from its entry's signature without holding a
+/// tracer lock, since that runs Python and every command on the tracer waits
+/// on its lock. Sets `entry_kind` when a tracer may be spawned without one, and
+/// returns the entry's source lines for `check_stop_line`, cached on the
+/// running tracer when it has them. `None` when unknown, and for `at_commit`
+/// requests, whose function may sit elsewhere in the old checkout (their kind
+/// is detected there when the tracer spawns).
+fn resolve_entry(req: &mut TraceRequest, tracers: &SharedTracer, config: &AppConfig) -> Result<Option<(u32, u32)>, FlowlensError> {
+    if req.at_commit.is_some() {
+        return Ok(None);
+    }
+    let (cached_span, continues) = match tracer_slot(tracers, &req.key()) {
+        Some(slot) => (
+            *slot.source_span.lock().unwrap(),
+            !slot.tracer.lock().unwrap().prewarmed && !req.restart,
+        ),
+        None => (None, false),
+    };
+    let wants_span = req.stop_line > 0 && cached_span.is_none();
+    let wants_kind = req.entry_kind.is_none() && !continues;
+    let span = if wants_span || wants_kind {
+        let signature = run_signature(config, &req.entry_full_id, &[]);
+        let span = signature.as_ref().ok().and_then(|signature| signature.start_line.zip(signature.end_line));
+        if wants_kind {
+            req.entry_kind = Some(EntryKind::of(signature?, &req.entry_full_id)?);
+        }
+        span
+    } else {
+        cached_span
+    };
+    Ok(span.filter(|_| req.stop_line > 0))
+}
+
+/// Reject a `stop_line` outside `span`, which the tracer would run straight
+/// past. The line just after the end is fine: the UI asks for `line + 1` to run
+/// the last line, which stops at the return. With `allow_out_of_range` the
+/// error comes back as a warning instead.
+fn check_stop_line(req: &TraceRequest, span: Option<(u32, u32)>) -> Result<Option<String>, FlowlensError> {
+    let Some((start_line, end_line)) = span else {
+        return Ok(None);
+    };
+    if (start_line..=end_line + 1).contains(&(req.stop_line as u32)) {
+        return Ok(None);
+    }
+    let error = FlowlensError::BreakpointOutOfRange {
+        entry_full_id: req.entry_full_id.clone(),
+        stop_line: req.stop_line,
+        start_line,
+        end_line,
+    };
+    if req.allow_out_of_range {
+        println!("[Rust] warning: {}", error);
+        Ok(Some(error.to_string()))
+    } else {
+        Err(error)
+    }
+}
+
+#[tauri::command]
+fn get_tracer_data(
+    mut req: TraceRequest,
+    tracer_state: State<SharedTracer>,
+    config: State<SharedConfig>,
+    recent: State<SharedRecentFlows>,
+    pending: State<PendingCommands>,
+) -> Result<Value, FlowlensError> {
+    let _pending = pending.start(&req.key());
+    println!("[Rust] get_tracer_data called");
+    println!("[Rust] req.entry_full_id = {}", req.entry_full_id);
+    println!("[Rust] req.args_json = {}", req.args_json);
+    println!("[Rust] req.stop_line = {}", req.stop_line);
+    println!("[Rust] req.setup_expr = {:?}", req.setup_expr);
+    println!("[Rust] req.request_id = {:?}", req.request_id);
+
+    req.validate()?;
+    let config = config.lock().unwrap().clone();
+    config.tracer_backend(req.tracer_backend.as_deref())?;
+
+    let span = resolve_entry(&mut req, &tracer_state, &config)?;
+    let warning = check_stop_line(&req, span)?;
+
+    let flow = req.key();
+    println!("[Rust] tracer alive = {}", tracer_slot(&tracer_state, &flow).is_some());
+
+    let retries = config.spawn_retries();
+    let mut attempt = 0;
+    let mut spawn_reason = None;
+    let mut is_first_call = false;
+    let (mut event, previous) = loop {
+        let result = ensure_tracer(&tracer_state, &req, &config, Tracer::spawn, |tracer, reason| {
+            // A freshly spawned tracer sends its initial event unprompted.
+            // Retries respawn the tracer that died; report why the first attempt spawned.
+            spawn_reason = spawn_reason.or(reason);
+            is_first_call = reason.is_some();
+
+            println!("[Rust] Current flow = {:?}", tracer.current_flow);
+
+            // Send continue command
+            if !is_first_call {
+                // "0" would tell Python to exit
+                if req.stop_line <= 0 {
+                    return Err(FlowlensError::InvalidRequest {
+                        message: "stop_line is required to continue a running trace".to_string(),
+                    });
+                }
+                println!("[Rust] Sending continue_to {}", req.stop_line);
+
+                tracer.send_line(req.stop_line.to_string())?;
+            } else {
+                println!("[Rust] First call for this function — Python will send initial event");
+            }
+
+            let previous = tracer.last_event.clone().filter(|_| req.delta && !is_first_call);
+            let configured_timeout = tracer.read_timeout;
+            if let Some(ms) = req.timeout_ms {
+                tracer.read_timeout = Duration::from_millis(ms);
+            }
+            let result = tracer.read_step_event(&config.payload_limits);
+            tracer.read_timeout = configured_timeout;
+            Ok((result?, previous))
+        });
+        match result {
+            // Python died before its first event: likely a flaky import, so respawn
+            Err(FlowlensError::PythonExited { .. }) if is_first_call && attempt < retries => {
+                attempt += 1;
+                println!(
+                    "[flowlens] tracer exited on spawn, retrying ({}/{})",
+                    attempt, retries
+                );
+                std::thread::sleep(SPAWN_RETRY_BACKOFF * attempt);
+            }
+            result => break result?,
+        }
+    };
+
+    if let (Some(span), Some(slot)) = (span, tracer_slot(&tracer_state, &flow)) {
+        slot.source_span.lock().unwrap().get_or_insert(span);
+    }
+    // Only for a trace that started, and with no tracer locked while it is saved
+    if is_first_call {
+        recent.lock().unwrap().record(&req);
+    }
+    if let Some(warning) = &warning {
+        event["stop_line_warning"] = json!(warning);
+    }
+    if let Some(previous) = &previous {
+        event = delta::encode(previous, &event);
+    }
+    if let Some(request_id) = &req.request_id {
+        event["request_id"] = json!(request_id);
+    }
+    event["spawn_info"] = json!({ "spawned": is_first_call, "reason": spawn_reason });
+    Ok(event)
+}
+
+/// Start tracing the `index`th (0-based) changed function of the last
+/// `get_flows` result, in id order, so a diff can be stepped through function
+/// by function. Returns the chosen `entry_full_id` and the first `event`.
+#[tauri::command]
+fn trace_next_changed(
+    index: usize,
+    args_json: String,
+    stop_line: i32,
+    app: AppHandle,
+) -> Result<Value, FlowlensError> {
+    println!("[Rust] trace_next_changed #{}", index);
+    let entry_full_id = {
+        let changed = app.state::<SharedChangedFunctions>();
+        let changed = changed.lock().unwrap();
+        if changed.is_empty() {
+            return Err(FlowlensError::InvalidRequest {
+                message: "no changed functions loaded; call get_flows first".to_string(),
+            });
+        }
+        changed.get(index).cloned().ok_or_else(|| FlowlensError::InvalidRequest {
+            message: format!("index {} is out of range: {} changed functions", index, changed.len()),
+        })?
+    };
+
+    let req = TraceRequest {
+        entry_full_id: entry_full_id.clone(),
+        args_json,
+        stop_line,
+        ..Default::default()
+    };
+    let event = get_tracer_data(req, app.state(), app.state(), app.state(), app.state())?;
+    Ok(json!({ "entry_full_id": entry_full_id, "event": event }))
+}
+
+/// Run `req`'s function to completion in a fresh tracer process, as a quick
+/// crash check or micro-benchmark. Returns the end event (`return_value` or
+/// `exception`, and `steps`: lines of repo code executed) with `duration_ms`,
+/// the wall time of the whole session, and the process `exit_code`. The live
+/// tracer, if any, is left alone.
+#[tauri::command]
+fn run_to_end(req: TraceRequest, config: State<SharedConfig>) -> Result<Value, FlowlensError> {
+    println!("[Rust] run_to_end {} (request_id={:?})", req.entry_full_id, req.request_id);
+    req.validate()?;
+    let config = config.lock().unwrap().clone();
+    run_to_completion(&req, &config, &["--run_to_end"])
+}
+
+/// How many of the most executed lines `profile_run` lists as `hottest`.
+const PROFILE_HOTTEST_LINES: usize = 20;
+
+/// Run `req` to completion like `run_to_end`, counting how often each line of
+/// repo code ran. Adds `line_counts` (`{"file:line": count}`), per-file totals
+/// in `by_file`, and the most executed lines as `hottest`, busiest first.
+#[tauri::command]
+fn profile_run(
+    req: TraceRequest,
+    config: State<SharedConfig>,
+    coverage: State<SharedCoverage>,
+) -> Result<Value, FlowlensError> {
+    println!("[Rust] profile_run {} (request_id={:?})", req.entry_full_id, req.request_id);
+    req.validate()?;
+    let config = config.lock().unwrap().clone();
+    let mut event = run_to_completion(&req, &config, &["--run_to_end", "--profile"])?;
+
+    let mut by_file: BTreeMap<String, u64> = BTreeMap::new();
+    let mut lines: Vec<(String, u64, u64)> = Vec::new();
+    for (key, count) in event.get("line_counts").and_then(Value::as_object).into_iter().flatten() {
+        let (Some((file, line)), Some(count)) = (key.rsplit_once(':'), count.as_u64()) else {
+            continue;
+        };
+        *by_file.entry(file.to_string()).or_default() += count;
+        lines.push((file.to_string(), line.parse().unwrap_or(0), count));
+    }
+    lines.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| (&a.0, a.1).cmp(&(&b.0, b.1))));
+    let hottest: Vec<Value> = lines
+        .into_iter()
+        .take(PROFILE_HOTTEST_LINES)
+        .map(|(file, line, count)| json!({ "file": file, "line": line, "count": count }))
+        .collect();
+
+    event["by_file"] = json!(by_file);
+    event["hottest"] = json!(hottest);
+    coverage
+        .lock()
+        .unwrap()
+        .insert(normalize_entry_id(&req.entry_full_id), line_coverage(&event));
+    Ok(event)
+}
+
+/// Per file, how often each line ran in the last `profile_run` of each flow.
+type SharedCoverage = Mutex<HashMap<String, BTreeMap<String, BTreeMap<u64, u64>>>>;
+
+/// `line_counts` of a profile end event by file, plus a 0 for every
+/// `executable_lines` entry that never ran.
+fn line_coverage(event: &Value) -> BTreeMap<String, BTreeMap<u64, u64>> {
+    let mut coverage: BTreeMap<String, BTreeMap<u64, u64>> = BTreeMap::new();
+    for (file, lines) in event.get("executable_lines").and_then(Value::as_object).into_iter().flatten() {
+        let counts = coverage.entry(file.clone()).or_default();
+        for line in lines.as_array().into_iter().flatten().filter_map(Value::as_u64) {
+            counts.insert(line, 0);
+        }
+    }
+    for (key, count) in event.get("line_counts").and_then(Value::as_object).into_iter().flatten() {
+        let (Some((file, line)), Some(count)) = (key.rsplit_once(':'), count.as_u64()) else {
+            continue;
+        };
+        if let Ok(line) = line.parse() {
+            coverage.entry(file.to_string()).or_default().insert(line, count);
+        }
+    }
+    coverage
+}
+
+/// Execution count of each line of `file` in the last `profile_run` of
+/// `entry_full_id`, for a coverage gutter. Lines of functions that ran but
+/// never reached count 0; lines outside them are left out.
+#[tauri::command]
+fn file_coverage(
+    entry_full_id: String,
+    file: String,
+    config: State<SharedConfig>,
+    coverage: State<SharedCoverage>,
+) -> Result<Value, FlowlensError> {
+    println!("[Rust] file_coverage {} in {}", file, entry_full_id);
+    let invalid = |message: String| FlowlensError::InvalidRequest { message };
+    let repo = config.lock().unwrap().repo();
+    let root = repo
+        .canonicalize()
+        .map_err(|e| invalid(format!("cannot resolve repo {}: {}", repo.display(), e)))?;
+    let path = root
+        .join(file.trim_start_matches('/'))
+        .canonicalize()
+        .map_err(|e| invalid(format!("file {} not found: {}", file, e)))?;
+    let Ok(relative) = path.strip_prefix(&root) else {
+        return Err(invalid(format!("file {} is outside the repo", file)));
+    };
+    let relative = relative.to_string_lossy().replace('\\', "/");
+
+    let coverage = coverage.lock().unwrap();
+    let runs = coverage.get(&normalize_entry_id(&entry_full_id)).ok_or_else(|| {
+        invalid(format!("no profile_run of {} yet", entry_full_id))
+    })?;
+    let lines: Vec<Value> = runs
+        .get(&relative)
+        .into_iter()
+        .flatten()
+        .map(|(line, count)| json!({ "line": line, "count": count }))
+        .collect();
+    let executed = lines.iter().filter(|line| line["count"].as_u64() != Some(0)).count();
+    Ok(json!({
+        "entry_full_id": entry_full_id,
+        "file": relative,
+        "lines": lines,
+        "executed": executed,
+        "total": lines.len(),
+    }))
+}
+
+/// Spawn the tracer with `modes` (which must include `--run_to_end`), wait for
+/// it to exit and return its end event with timing attached.
+fn run_to_completion(req: &TraceRequest, config: &AppConfig, modes: &[&str]) -> Result<Value, FlowlensError> {
+    let started = Instant::now();
+    let (config, _worktree) = checkout_for(req, config)?;
+    let (mut cmd, _) = trace_command(req, &config)?;
+    let output = run_tool(cmd.args(modes))?;
+    let duration_ms = started.elapsed().as_millis() as u64;
+
+    let (events, diagnostics) = match config.events_on {
+        EventChannel::Stdout => (&output.stdout, &output.stderr),
+        EventChannel::Stderr => (&output.stderr, &output.stdout),
+    };
+    // The traced code may print on the events channel too; the event is the last JSON line
+    let mut event = String::from_utf8_lossy(events)
+        .lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str::<Value>(line.trim()).ok())
+        .find(|value| value.get("event").is_some())
+        .ok_or_else(|| FlowlensError::ScriptFailed {
+            script: tools::TRACER.to_string(),
+            code: output.status.code(),
+            stderr: format!("exited without an end event: {}", String::from_utf8_lossy(diagnostics).trim()),
+        })?;
+
+    event["duration_ms"] = json!(duration_ms);
+    event["exit_code"] = json!(output.status.code());
+    if let Some(request_id) = &req.request_id {
+        event["request_id"] = json!(request_id);
+    }
+    RepoPaths::new(&config.repo()).relativize(&mut event);
+    enforce_limits(&mut event, &config.payload_limits);
+    Ok(event)
+}
+
+/// Continue the active trace until `target_function` is entered (by name, in
+/// any file) or the entry function returns. The event at entry carries the
+/// call `stack`, outermost frame first; `reached` is false if it never ran.
+#[tauri::command]
+fn run_to_function(
+    entry_full_id: String,
+    target_function: String,
+    tracer_state: State<SharedTracer>,
+    config: State<SharedConfig>,
+    pending: State<PendingCommands>,
+) -> Result<Value, FlowlensError> {
+    let _pending = pending.start(&normalize_entry_id(&entry_full_id));
+    println!("[Rust] run_to_function {} -> {}", entry_full_id, target_function);
+    let target_function = target_function.trim();
+    if target_function.is_empty() || !target_function.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return Err(FlowlensError::InvalidRequest {
+            message: format!("not a function name: {:?}", target_function),
+        });
+    }
+    let limits = config.lock().unwrap().payload_limits;
+
+    with_active_tracer(&tracer_state, &entry_full_id, |tracer| {
+        tracer.send_line(format!("run_to {}", target_function))?;
+        tracer.read_step_event(&limits)
+    })
+}
+
+/// Continue the active trace until the paused frame returns and pause at the
+/// next line of its caller. That event names the `finished_function` and
+/// carries its `return_value`; finishing the entry function itself gives the
+/// usual completion event.
+#[tauri::command]
+fn finish(
+    entry_full_id: String,
+    tracer_state: State<SharedTracer>,
+    config: State<SharedConfig>,
+    pending: State<PendingCommands>,
+) -> Result<Value, FlowlensError> {
+    let _pending = pending.start(&normalize_entry_id(&entry_full_id));
+    println!("[Rust] finish {}", entry_full_id);
+    let limits = config.lock().unwrap().payload_limits;
+
+    with_active_tracer(&tracer_state, &entry_full_id, |tracer| {
+        tracer.send_line("finish".to_string())?;
+        tracer.read_step_event(&limits)
+    })
+}
+
+/// A step over and what it did to the locals.
+#[derive(Serialize, Debug)]
+struct SteppedOver {
+    event: Value,
+    /// Locals as `{added, changed, removed}` against the step before; null when
+    /// the step ended without a frame (completion, error)
+    diff: Option<Value>,
+    /// Set when the stepped-over call raised: `event` then pauses where the
+    /// exception reached the frame, and `diff` holds what changed up to there
+    exception: Option<Value>,
+}
+
+/// Continue the active trace to the next line of the paused frame, running the
+/// calls on this line without pausing (breakpoints still pause). Past the last
+/// line it pauses at the next line of the caller, or completes. Returns the
+/// event together with the change in locals since the step before.
+#[tauri::command]
+fn step_over_with_diff(
+    entry_full_id: String,
+    tracer_state: State<SharedTracer>,
+    config: State<SharedConfig>,
+    pending: State<PendingCommands>,
+) -> Result<SteppedOver, FlowlensError> {
+    let _pending = pending.start(&normalize_entry_id(&entry_full_id));
+    println!("[Rust] step_over_with_diff {}", entry_full_id);
+    let limits = config.lock().unwrap().payload_limits;
+
+    // Compare the events as sent, so both sides went through the same filters and limits
+    let empty = Map::new();
+    let (before, event) = with_active_tracer(&tracer_state, &entry_full_id, |tracer| {
+        let before = tracer.last_event.as_ref().and_then(|e| e.get("locals")).and_then(Value::as_object).cloned();
+        tracer.send_line("step_over".to_string())?;
+        Ok((before, tracer.read_step_event(&limits)?))
+    })?;
+
+    let diff = event
+        .get("locals")
+        .and_then(Value::as_object)
+        .map(|after| delta::diff(before.as_ref().unwrap_or(&empty), after));
+    let exception = match Phase::of(&event)? {
+        Some(Phase::Exception) => event.get("exception").cloned(),
+        _ => None,
+    };
+    Ok(SteppedOver { event, diff, exception })
+}
+
+/// How many stepping commands (`get_tracer_data`, `finish`, `run_to_function`,
+/// `step_over_with_diff`, `collect_until`) for `entry_full_id` are running or waiting their turn, so
+/// the UI can hold the step button until the last one is answered. Answers
+/// immediately, even while a step is running.
+#[tauri::command]
+fn tracer_queue_depth(entry_full_id: String, pending: State<PendingCommands>) -> usize {
+    pending.depth(&normalize_entry_id(&entry_full_id))
+}
+
+/// Upper bound on the steps one `collect_until` call may take.
+const MAX_COLLECT_STEPS: u32 = 1000;
+
+/// Step the active trace one line of the entry file at a time, collecting
+/// every event, until it stops at or past `target_line` (the event a plain
+/// continue to `target_line` would give), the function completes, an error
+/// comes back, or `max` steps were taken. `reached` says whether the target
+/// was hit; stops at breakpoints in other files are collected but never
+/// count as reaching it.
+#[tauri::command]
+fn collect_until(
+    entry_full_id: String,
+    target_line: i32,
+    max: u32,
+    tracer_state: State<SharedTracer>,
+    config: State<SharedConfig>,
+    pending: State<PendingCommands>,
+) -> Result<Value, FlowlensError> {
+    let _pending = pending.start(&normalize_entry_id(&entry_full_id));
+    println!("[Rust] collect_until {} -> line {} (max {})", entry_full_id, target_line, max);
+    if target_line <= 0 {
+        return Err(FlowlensError::InvalidRequest {
+            message: format!("target_line must be at least 1, got {}", target_line),
+        });
+    }
+    if max == 0 || max > MAX_COLLECT_STEPS {
+        return Err(FlowlensError::InvalidRequest {
+            message: format!("max must be between 1 and {}, got {}", MAX_COLLECT_STEPS, max),
+        });
+    }
+    let limits = config.lock().unwrap().payload_limits;
+
+    with_active_tracer(&tracer_state, &entry_full_id, |tracer| {
+        let mut events = Vec::new();
+        let mut reached = false;
+        for _ in 0..max {
+            // Continuing to line 1 pauses at the very next line run in the entry file
+            tracer.send_line("1".to_string())?;
+            let event = tracer.read_step_event(&limits)?;
+            let done = event.get("completed").and_then(Value::as_bool) == Some(true)
+                || event.get("event").and_then(Value::as_str) == Some("error");
+            reached = event.get("breakpoint").is_none()
+                && event.get("line").and_then(Value::as_i64).is_some_and(|line| line >= i64::from(target_line));
+            events.push(event);
+            if done || reached {
+                break;
+            }
+        }
+        Ok(json!({ "events": events, "reached": reached, "steps": events.len() }))
+    })
+}
+
+/// Trace `req` on a background thread and emit every step as a `trace-event`,
+/// tagged with its `entry_full_id`, instead of answering one step per call.
+/// The first event is what `get_tracer_data` would return for `req`; from
+/// there the trace continues line by line in the entry file, pausing at
+/// breakpoints too. The stream ends with a `trace-event`
+/// `{entry_full_id, done: true, stopped}` once the function completes or
+/// `stop_trace_stream` stops it, or with a `trace-error` (the error plus its
+/// `message`, which includes the tracer's output) when a step fails. Counts as
+/// one pending stepping command while it runs.
+#[tauri::command]
+fn start_trace_stream(req: TraceRequest, app: AppHandle, streams: State<TraceStreams>) -> Result<(), FlowlensError> {
+    println!("[Rust] start_trace_stream {}", req.entry_full_id);
+    req.validate()?;
+    let flow = req.key();
+    let Some(stop) = streams.start(&flow) else {
+        return Err(FlowlensError::InvalidRequest {
+            message: format!("a trace stream for {} is already running", flow),
+        });
+    };
+
+    thread::spawn(move || {
+        let pending = app.state::<PendingCommands>();
+        let _pending = pending.start(&flow);
+        let result = stream_trace(&app, req, &flow, &stop);
+        app.state::<TraceStreams>().finished(&flow);
+        let (name, payload) = match result {
+            Ok(stopped) => ("trace-event", json!({ "entry_full_id": flow, "done": true, "stopped": stopped })),
+            Err(e) => {
+                println!("[flowlens] trace stream for {} failed: {}", flow, e);
+                let mut payload = json!(e);
+                payload["entry_full_id"] = json!(flow);
+                payload["message"] = json!(e.to_string());
+                ("trace-error", payload)
+            }
+        };
+        if let Err(e) = app.emit(name, payload) {
+            println!("[flowlens] failed to emit {}: {}", name, e);
+        }
+    });
+    Ok(())
+}
+
+/// Body of a `start_trace_stream` thread: emit events until the function
+/// completes or `stop` is set. Returns whether `stop` ended it.
+fn stream_trace(app: &AppHandle, req: TraceRequest, flow: &str, stop: &AtomicBool) -> Result<bool, FlowlensError> {
+    let limits = app.state::<SharedConfig>().lock().unwrap().payload_limits;
+    let mut event = get_tracer_data(req, app.state(), app.state(), app.state(), app.state())?;
+    loop {
+        if event.get("event").and_then(Value::as_str) == Some("error") {
+            let message = event.get("error").and_then(Value::as_str).unwrap_or("tracer reported an error");
+            return Err(FlowlensError::Tracer {
+                message: message.to_string(),
+            });
+        }
+        let completed = event.get("completed").and_then(Value::as_bool) == Some(true);
+        event["entry_full_id"] = json!(flow);
+        if let Err(e) = app.emit("trace-event", &event) {
+            println!("[flowlens] failed to emit trace-event: {}", e);
+        }
+        if completed {
+            return Ok(false);
+        }
+        if stop.load(Ordering::SeqCst) {
+            return Ok(true);
+        }
+
+        // Locked per step, so inspection commands can run between steps
+        event = with_active_tracer(&app.state::<SharedTracer>(), flow, |tracer| {
+            // Continuing to line 1 pauses at the very next line run in the entry file
+            tracer.send_line("1".to_string())?;
+            tracer.read_step_event(&limits)
+        })?;
+    }
+}
+
+/// Stop the `start_trace_stream` for `entry_full_id` after its current step;
+/// the tracer stays paused there. Returns whether a stream was running.
+#[tauri::command]
+fn stop_trace_stream(entry_full_id: String, streams: State<TraceStreams>) -> bool {
+    println!("[Rust] stop_trace_stream {}", entry_full_id);
+    streams.stop(&normalize_entry_id(&entry_full_id))
+}
+
+
+
+
+/// Output of `get_tracer.py --get_signature`. Lookup failures come back as `error`
+/// with an otherwise empty signature, which the UI already checks for.
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct FunctionSignature {
+    #[serde(default)]
+    params: Vec<String>,
+    #[serde(default)]
+    param_count: usize,
+    #[serde(default)]
+    kind: Option<String>,
+    #[serde(default)]
+    decorators: Vec<String>,
+    /// Repo-relative file and line span of the definition, decorators included
+    #[serde(default)]
+    file: Option<String>,
+    #[serde(default)]
+    start_line: Option<u32>,
+    #[serde(default)]
+    end_line: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    complexity: Option<FunctionMetrics>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// Echo of the caller's `request_id`; never set by Python
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+}
+
+/// AST-derived counts reported by `--with_complexity`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct FunctionMetrics {
+    /// `if`/`elif`, conditional expressions, `except` handlers and `match` cases
+    branches: u32,
+    /// `for`/`while` loops and comprehensions
+    loops: u32,
+    /// `and`/`or` operands beyond the first, which also add paths
+    boolean_ops: u32,
+    line_count: u32,
+    /// McCabe complexity: 1 + branches + loops + boolean_ops
+    cyclomatic: u32,
+}
+
+/// Run `get_tracer.py --get_signature`, adding optional modes such as `--with_decorators`.
+fn run_signature(config: &AppConfig, entry_full_id: &str, modes: &[&str]) -> Result<FunctionSignature, FlowlensError> {
+    run_tracer_query(config, entry_full_id, &[&["--get_signature"], modes].concat())
+}
+
+/// Run a one-shot, non-tracing mode of `get_tracer.py` (e.g. `--get_callees`)
+/// and parse the JSON it prints on stdout. A process killed by a signal
+/// (OOM killer, a stray `kill`) is re-run once, since the query has no side effects.
+fn run_tracer_query<T: DeserializeOwned>(config: &AppConfig, entry_full_id: &str, mode_args: &[&str]) -> Result<T, FlowlensError> {
+    let repo = require_repo(config)?;
+    let python = config.checked_python()?;
+    let script_path = config.resolve_script(tools::TRACER)?;
+    
+    let mut cmd = config.python_command(&python.path);
+    cmd.arg("-u")
+        .arg(script_path)
+        .arg("--repo_root")
+        .arg(&repo)
+        .arg("--entry_full_id")
+        .arg(entry_full_id)
+        .args(config.sys_path.iter().flat_map(|dir| ["--sys-path", dir.as_str()]))
+        .args(mode_args);
+
+    let mut output = run_tool(&mut cmd)?;
+    if output.status.code().is_none() {
+        println!("[flowlens] {} was killed ({}), running it again", mode_args.join(" "), output.status);
+        output = run_tool(&mut cmd)?;
+    }
+
+    tool_json(&format!("{} {}", tools::TRACER, mode_args.join(" ")), &output)
+}
+
+#[tauri::command]
+fn get_function_signature(
+    entry_full_id: String,
+    with_decorators: Option<bool>,
+    request_id: Option<String>,
+    config: State<SharedConfig>,
+) -> Result<FunctionSignature, FlowlensError> {
+    println!("[Rust] get_function_signature called with entry_full_id = {} (request_id={:?})", entry_full_id, request_id);
+    
+    let config = config.lock().unwrap().clone();
+    let modes: &[&str] = if with_decorators.unwrap_or(false) { &["--with_decorators"] } else { &[] };
+    let mut signature = run_signature(&config, &entry_full_id, modes)?;
+    signature.request_id = request_id;
+    Ok(signature)
+}
+
+/// An entry id broken into the parts the UI shows as breadcrumbs.
+#[derive(Serialize, Debug)]
+struct EntryIdParts {
+    /// Dotted module path, e.g. `pkg.orders` for `/pkg/orders.py`
+    module: String,
+    /// Everything after `::`, e.g. `Order.total`
+    qualname: String,
+    /// Enclosing classes in the qualname, outermost first
+    classes: Vec<String>,
+    function: String,
+    /// Repo-relative file, from the signature lookup when it succeeds
+    file: String,
+    /// First line of the definition; `None` when the lookup failed
+    line: Option<u32>,
+}
+
+/// Split `entry_full_id` into module, qualname and function. The structure
+/// comes from the id itself; only `file`/`line` need Python, and a failed
+/// lookup leaves `line` empty rather than failing the command.
+#[tauri::command]
+fn parse_entry_id(entry_full_id: String, config: State<SharedConfig>) -> Result<EntryIdParts, FlowlensError> {
+    let normalized = normalize_entry_id(&entry_full_id);
+    let Some((path, qualname)) = normalized.split_once("::").filter(|(_, q)| !q.is_empty()) else {
+        return Err(FlowlensError::InvalidRequest {
+            message: format!("{} is not of the form /path/to/file.py::function", entry_full_id),
+        });
+    };
+
+    let file = path.trim_start_matches('/');
+    let mut module: Vec<&str> = file.trim_end_matches(".py").split('/').collect();
+    if module.len() > 1 && module.last() == Some(&"__init__") {
+        module.pop();
+    }
+    let mut scopes: Vec<String> = qualname.split('.').map(str::to_string).collect();
+    let function = scopes.pop().unwrap_or_default();
+
+    let config = config.lock().unwrap().clone();
+    let signature = run_signature(&config, &entry_full_id, &[]).unwrap_or_default();
+    if let Some(err) = &signature.error {
+        println!("[Rust] parse_entry_id: signature lookup failed for {}: {}", entry_full_id, err);
+    }
+
+    Ok(EntryIdParts {
+        module: module.join("."),
+        qualname: qualname.to_string(),
+        classes: scopes,
+        function,
+        file: signature.file.unwrap_or_else(|| file.to_string()),
+        line: signature.start_line,
+    })
+}
+
+/// Output of `get_tracer.py --check_import`.
+#[derive(Serialize, Deserialize, Debug)]
+struct ImportCheck {
+    importable: bool,
+    /// `ExceptionType: message` when the import failed
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    traceback: Option<String>,
+}
+
+/// Import the module of `entry_full_id` without calling anything, so a syntax
+/// error or missing dependency shows up before tracing instead of mid-trace.
+/// The module's import-time code does run.
+#[tauri::command]
+fn check_importable(entry_full_id: String, config: State<SharedConfig>) -> Result<ImportCheck, FlowlensError> {
+    println!("[Rust] check_importable called with entry_full_id = {}", entry_full_id);
+
+    let config = config.lock().unwrap().clone();
+    run_tracer_query(&config, &entry_full_id, &["--check_import"])
+}
+
+/// An I/O call found by `--analyze`.
+#[derive(Serialize, Deserialize, Debug)]
+struct IoCall {
+    name: String,
+    line: u32,
+    /// The I/O module it goes through; `None` for builtins like `open`
+    #[serde(default)]
+    module: Option<String>,
+}
+
+/// Output of `get_tracer.py --analyze`, plus the warnings derived from it.
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct TraceEstimate {
+    #[serde(default)]
+    line_count: u32,
+    #[serde(default)]
+    loops: u32,
+    #[serde(default)]
+    max_loop_depth: u32,
+    #[serde(default)]
+    recursive: bool,
+    #[serde(default)]
+    io_calls: Vec<IoCall>,
+    #[serde(default)]
+    io_modules: Vec<String>,
+    /// Human-readable heads-ups for the UI, empty when nothing stands out
+    #[serde(default)]
+    warnings: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Functions longer than this get a warning from `estimate_trace`.
+const LONG_FUNCTION_LINES: u32 = 100;
+
+/// Static hints, from the AST alone, about whether tracing `entry_full_id`
+/// may be slow or have side effects: its length, loop nesting, recursion and
+/// I/O calls, summarized as `warnings`.
+#[tauri::command]
+fn estimate_trace(entry_full_id: String, config: State<SharedConfig>) -> Result<TraceEstimate, FlowlensError> {
+    println!("[Rust] estimate_trace called with entry_full_id = {}", entry_full_id);
+
+    let config = config.lock().unwrap().clone();
+    let mut estimate: TraceEstimate = run_tracer_query(&config, &entry_full_id, &["--analyze"])?;
+    if let Some(err) = &estimate.error {
+        return Err(FlowlensError::InvalidRequest {
+            message: format!("Could not analyse {}: {}", entry_full_id, err),
+        });
+    }
+
+    if !estimate.io_calls.is_empty() {
+        let via = if estimate.io_modules.is_empty() {
+            String::new()
+        } else {
+            format!(" (via {})", estimate.io_modules.join(", "))
+        };
+        estimate.warnings.push(format!("does I/O{}: {} call(s)", via, estimate.io_calls.len()));
+    }
+    if estimate.max_loop_depth > 1 {
+        estimate.warnings.push(format!("has loops nested {} deep", estimate.max_loop_depth));
+    }
+    if estimate.recursive {
+        estimate.warnings.push("is recursive".to_string());
+    }
+    if estimate.line_count > LONG_FUNCTION_LINES {
+        estimate.warnings.push(format!("is long ({} lines)", estimate.line_count));
+    }
+    Ok(estimate)
+}
+
+/// Source of a function and the lines a breakpoint can stop on, from `--source`.
+#[derive(Deserialize)]
+struct FunctionSource {
+    #[serde(default)]
+    file: String,
+    #[serde(default)]
+    start_line: u32,
+    #[serde(default)]
+    end_line: u32,
+    #[serde(default)]
+    source: String,
+    #[serde(default)]
+    valid_lines: Vec<u32>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// Everything the code pane needs for `entry_full_id` in one call: its
+/// `source` (starting at `start_line`), the `current_line` the trace is paused
+/// on in it, `breakpoint_lines` in it, `valid_lines` a breakpoint can use, and
+/// `executed_lines`. The last three come from the active trace of this flow,
+/// or else from its last `profile_run`; without either they are empty.
+#[tauri::command]
+fn annotated_source(
+    entry_full_id: String,
+    config: State<SharedConfig>,
+    tracer_state: State<SharedTracer>,
+    coverage: State<SharedCoverage>,
+) -> Result<Value, FlowlensError> {
+    println!("[Rust] annotated_source {}", entry_full_id);
+    let config = config.lock().unwrap().clone();
+    let source: FunctionSource = run_tracer_query(&config, &entry_full_id, &["--source"])?;
+    if let Some(err) = source.error {
+        return Err(FlowlensError::InvalidRequest {
+            message: format!("Could not locate {}: {}", entry_full_id, err),
+        });
+    }
+    let in_function = |line: u64| (u64::from(source.start_line)..=u64::from(source.end_line)).contains(&line);
+
+    let flow = normalize_entry_id(&entry_full_id);
+    let mut current_line = None;
+    let mut breakpoint_lines = BTreeSet::new();
+    let mut executed_lines = BTreeSet::new();
+    match tracer_slot(&tracer_state, &flow) {
+        Some(slot) => {
+            let tracer = slot.tracer.lock().unwrap();
+            current_line = tracer
+                .last_event
+                .as_ref()
+                .filter(|event| event.get("filename").and_then(Value::as_str) == Some(source.file.as_str()))
+                .and_then(|event| event.get("line").and_then(Value::as_u64))
+                .filter(|line| in_function(*line));
+            breakpoint_lines.extend(
+                tracer
+                    .breakpoints
+                    .iter()
+                    .filter(|bp| bp.file.trim_start_matches('/') == source.file)
+                    .map(|bp| u64::from(bp.line))
+                    .filter(|line| in_function(*line)),
+            );
+            executed_lines.extend(
+                tracer
+                    .history
+                    .iter()
+                    .filter(|step| step.phase == Phase::Line && step.file == source.file)
+                    .map(|step| step.line),
+            );
+        }
+        None => {
+            let coverage = coverage.lock().unwrap();
+            let counts = coverage.get(&flow).and_then(|files| files.get(&source.file));
+            executed_lines.extend(
+                counts
+                    .into_iter()
+                    .flatten()
+                    .filter(|(_, count)| **count > 0)
+                    .map(|(line, _)| *line),
+            );
+        }
+    }
+    executed_lines.retain(|line| in_function(*line));
+
+    Ok(json!({
+        "entry_full_id": entry_full_id,
+        "file": source.file,
+        "start_line": source.start_line,
+        "end_line": source.end_line,
+        "source": source.source,
+        "current_line": current_line,
+        "breakpoint_lines": breakpoint_lines,
+        "valid_lines": source.valid_lines,
+        "executed_lines": executed_lines,
+    }))
+}
+
+/// One call site in a function body, as reported by `--get_callees`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Callee {
+    /// Source text of the called expression, e.g. `helper` or `self.repo.save`
+    name: String,
+    /// Set when the callee is statically a top-level repo function, so it can be traced
+    entry_full_id: Option<String>,
+    line: u32,
+}
+
+#[derive(Deserialize)]
+struct CalleesResult {
+    #[serde(default)]
+    callees: Vec<Callee>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// Functions called by `entry_full_id`, found statically in its AST. Dynamic
+/// calls are still listed, just without an `entry_full_id`.
+#[tauri::command]
+fn get_callees(
+    entry_full_id: String,
+    config: State<SharedConfig>,
+    cache: State<SharedCalleesCache>,
+) -> Result<Vec<Callee>, FlowlensError> {
+    println!("[Rust] get_callees called with entry_full_id = {}", entry_full_id);
+
+    let config = config.lock().unwrap().clone();
+    let head = head_commit(&config.repo());
+    cached_callees(&config, head.as_deref(), &cache, &entry_full_id)
+}
+
+/// `get_callees` results keyed by (repo, HEAD commit, entry id), like `SharedCallersCache`.
+type SharedCalleesCache = Mutex<HashMap<(PathBuf, String, String), Vec<Callee>>>;
+
+/// Callees of `entry_full_id`, from `cache` when `head` is known and they were
+/// looked up at that commit before.
+fn cached_callees(
+    config: &AppConfig,
+    head: Option<&str>,
+    cache: &SharedCalleesCache,
+    entry_full_id: &str,
+) -> Result<Vec<Callee>, FlowlensError> {
+    let key = head.map(|head| (config.repo(), head.to_string(), normalize_entry_id(entry_full_id)));
+    if let Some(callees) = key.as_ref().and_then(|key| cache.lock().unwrap().get(key).cloned()) {
+        return Ok(callees);
+    }
+
+    let result: CalleesResult = run_tracer_query(config, entry_full_id, &["--get_callees"])?;
+    if let Some(err) = result.error {
+        return Err(FlowlensError::InvalidRequest {
+            message: format!("Could not analyse {}: {}", entry_full_id, err),
+        });
+    }
+    if let Some(key) = key {
+        cache.lock().unwrap().insert(key, result.callees.clone());
+    }
+    Ok(result.callees)
+}
+
+/// Most functions `call_graph` visits, so a wide graph can't spawn Python without bound.
+const MAX_CALL_GRAPH_NODES: usize = 200;
+
+/// Static call graph from `roots`, following callees that resolve to repo
+/// functions up to `depth` calls away (0 is just the roots), breadth first.
+/// Returns `nodes` (`{id, depth}`, plus `error` when its callees couldn't be
+/// read), `adjacency` (id to callee ids) and `truncated` when
+/// `MAX_CALL_GRAPH_NODES` cut the walk short. With `format: "dot"` the graph
+/// is also rendered as Graphviz text under `dot`.
+#[tauri::command]
+fn call_graph(
+    roots: Vec<String>,
+    depth: usize,
+    format: Option<String>,
+    config: State<SharedConfig>,
+    cache: State<SharedCalleesCache>,
+) -> Result<Value, FlowlensError> {
+    println!("[Rust] call_graph from {:?} (depth {})", roots, depth);
+    let dot = match format.as_deref() {
+        None | Some("json") => false,
+        Some("dot") => true,
+        Some(other) => {
+            return Err(FlowlensError::InvalidRequest {
+                message: format!("unknown call graph format {:?}; use json or dot", other),
+            })
+        }
+    };
+
+    let config = config.lock().unwrap().clone();
+    let head = head_commit(&config.repo());
+    let mut queue: VecDeque<(String, usize)> = VecDeque::new();
+    let mut depths: BTreeMap<String, usize> = BTreeMap::new();
+    for root in &roots {
+        let id = normalize_entry_id(root);
+        if !depths.contains_key(&id) {
+            depths.insert(id.clone(), 0);
+            queue.push_back((id, 0));
+        }
+    }
+
+    let mut nodes = Vec::new();
+    let mut adjacency: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut truncated = false;
+    while let Some((id, level)) = queue.pop_front() {
+        let mut node = json!({ "id": id, "depth": level });
+        let callees = if level < depth {
+            match cached_callees(&config, head.as_deref(), &cache, &id) {
+                Ok(callees) => callees,
+                Err(e) => {
+                    node["error"] = json!(e);
+                    Vec::new()
+                }
+            }
+        } else {
+            Vec::new()
+        };
+        nodes.push(node);
+
+        let targets = adjacency.entry(id).or_default();
+        for callee in callees {
+            let Some(target) = callee.entry_full_id.as_deref().map(normalize_entry_id) else {
+                continue;
+            };
+            if targets.contains(&target) {
+                continue;
+            }
+            if !depths.contains_key(&target) {
+                if depths.len() >= MAX_CALL_GRAPH_NODES {
+                    truncated = true;
+                    continue;
+                }
+                depths.insert(target.clone(), level + 1);
+                queue.push_back((target.clone(), level + 1));
+            }
+            targets.push(target);
+        }
+    }
+
+    let mut graph = json!({ "nodes": nodes, "adjacency": adjacency, "truncated": truncated });
+    if dot {
+        graph["dot"] = json!(call_graph_dot(&adjacency));
+    }
+    Ok(graph)
+}
+
+/// `adjacency` as a Graphviz digraph, one edge per line.
+fn call_graph_dot(adjacency: &BTreeMap<String, Vec<String>>) -> String {
+    let quote = |id: &str| format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""));
+    let mut dot = String::from("digraph calls {\n");
+    for (from, targets) in adjacency {
+        if targets.is_empty() {
+            dot.push_str(&format!("  {};\n", quote(from)));
+        }
+        for to in targets {
+            dot.push_str(&format!("  {} -> {};\n", quote(from), quote(to)));
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+#[derive(Deserialize)]
+struct CallersResult {
+    #[serde(default)]
+    callers: Vec<String>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// `get_callers` results keyed by (repo, HEAD commit, entry id).
+type SharedCallersCache = Mutex<HashMap<(PathBuf, String, String), Vec<String>>>;
+
+/// Commit checked out in `repo`, or `None` if it isn't a git repo.
+fn head_commit(repo: &Path) -> Option<String> {
+    let output = output_logged(Command::new("git").arg("-C").arg(repo).args(["rev-parse", "HEAD"])).ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Repo functions whose bodies statically call `entry_full_id`. Scanning the
+/// whole repo is slow, so results are cached per HEAD commit; uncommitted edits
+/// are only picked up after the next commit (or for repos without git history).
+#[tauri::command]
+fn get_callers(
+    entry_full_id: String,
+    config: State<SharedConfig>,
+    cache: State<SharedCallersCache>,
+) -> Result<Vec<String>, FlowlensError> {
+    println!("[Rust] get_callers called with entry_full_id = {}", entry_full_id);
+
+    let config = config.lock().unwrap().clone();
+    let repo = config.repo();
+    let key = head_commit(&repo).map(|head| (repo, head, normalize_entry_id(&entry_full_id)));
+    if let Some(callers) = key.as_ref().and_then(|key| cache.lock().unwrap().get(key).cloned()) {
+        println!("[Rust] get_callers: cache hit");
+        return Ok(callers);
+    }
+
+    let result: CallersResult = run_tracer_query(&config, &entry_full_id, &["--get_callers"])?;
+    if let Some(err) = result.error {
+        return Err(FlowlensError::InvalidRequest {
+            message: format!("Could not search callers of {}: {}", entry_full_id, err),
+        });
+    }
+    if let Some(key) = key {
+        cache.lock().unwrap().insert(key, result.callers.clone());
+    }
+    Ok(result.callers)
+}
+
+/// Static complexity hints for picking breakpoints in a function.
+#[tauri::command]
+fn function_metrics(entry_full_id: String, config: State<SharedConfig>) -> Result<FunctionMetrics, FlowlensError> {
+    println!("[Rust] function_metrics called with entry_full_id = {}", entry_full_id);
+
+    let config = config.lock().unwrap().clone();
+    let signature = run_signature(&config, &entry_full_id, &["--with_complexity"])?;
+    if let Some(err) = signature.error {
+        return Err(FlowlensError::InvalidRequest {
+            message: format!("Could not analyse {}: {}", entry_full_id, err),
+        });
+    }
+    signature
+        .complexity
+        .ok_or_else(|| FlowlensError::InvalidRequest {
+            message: format!("No source available to analyse {}", entry_full_id),
+        })
+}
+
+/// Point the app at a working copy of the Python tools, or back to the
+/// default with `None`. The directory must contain every script the app runs.
+#[tauri::command]
+fn set_tools_dir(path: Option<String>, config: State<SharedConfig>) -> Result<Value, FlowlensError> {
+    println!("[flowlens] set_tools_dir: {:?}", path);
+
+    let dir = path.as_deref().map(str::trim).filter(|p| !p.is_empty()).map(PathBuf::from);
+    if let Some(dir) = &dir {
+        let missing = missing_tools(dir);
+        if !missing.is_empty() {
+            return Err(FlowlensError::InvalidRequest {
+                message: format!("{} is missing {}", dir.display(), missing.join(", ")),
+            });
+        }
+    }
+
+    let mut config = config.lock().unwrap();
+    config.tools_dir = dir;
+    Ok(json!({ "tools_dir": config.tools_dir() }))
+}
+
+/// Run Python from now on with a clean environment (only `ISOLATED_ENV_KEEP`
+/// plus `env_overrides`) or the app's full environment. `env_overrides`
+/// replaces the previous overrides when given.
+#[tauri::command]
+fn set_env_isolation(
+    isolate_env: bool,
+    env_overrides: Option<HashMap<String, String>>,
+    config: State<SharedConfig>,
+) -> Result<(), FlowlensError> {
+    println!("[flowlens] set_env_isolation: isolate={} overrides={:?}", isolate_env, env_overrides);
+    if let Some(bad) = env_overrides.iter().flatten().map(|(k, _)| k).find(|k| k.is_empty() || k.contains('=')) {
+        return Err(FlowlensError::InvalidRequest {
+            message: format!("invalid environment variable name: {:?}", bad),
+        });
+    }
+
+    let mut config = config.lock().unwrap();
+    config.isolate_env = isolate_env;
+    if let Some(overrides) = env_overrides {
+        config.env_overrides = overrides;
+    }
+    Ok(())
+}
+
+/// Resolve the interpreter again, e.g. after creating or changing the repo's
+/// venv. Tracers already running keep the interpreter they started with.
+#[tauri::command]
+fn refresh_python(config: State<SharedConfig>) -> PythonInterpreter {
+    config.lock().unwrap().refresh_python().clone()
+}
+
+/// The interpreter commands will run, checked to be present and at least
+/// `MIN_PYTHON`. Reads what was resolved at startup or the last repo change
+/// or `refresh_python`; call that first to pick up a newly installed Python.
+#[tauri::command]
+fn check_python(config: State<SharedConfig>) -> Result<Value, FlowlensError> {
+    let python = config.lock().unwrap().checked_python()?;
+    Ok(json!({
+        "path": python.path,
+        "version": python.release,
+        "fallback": python.fallback,
+    }))
+}
+
+/// Set how long each read waits for the tracer's next event, in seconds. Applies
+/// to running tracers too. Returns the timeout now in effect.
+#[tauri::command]
+fn set_read_timeout(
+    secs: u64,
+    config: State<SharedConfig>,
+    tracer_state: State<SharedTracer>,
+    settings: State<SharedSettings>,
+) -> Result<u64, FlowlensError> {
+    println!("[flowlens] set_read_timeout: {}", secs);
+    if secs < MIN_READ_TIMEOUT_SECS {
+        return Err(FlowlensError::InvalidRequest {
+            message: format!("read timeout must be at least {}s", MIN_READ_TIMEOUT_SECS),
+        });
+    }
+    settings
+        .lock()
+        .unwrap()
+        .update(|saved| saved.read_timeout_secs = Some(secs))?;
+    config.lock().unwrap().read_timeout_secs = Some(secs);
+    for (_, slot) in tracer_slots(&tracer_state) {
+        slot.tracer.lock().unwrap().read_timeout = Duration::from_secs(secs);
+    }
+    Ok(secs)
+}
+
+/// Seconds each read waits for the tracer's next event.
+#[tauri::command]
+fn get_read_timeout(config: State<SharedConfig>) -> u64 {
+    config.lock().unwrap().read_timeout().as_secs()
+}
+
+/// Set how many tracers may run at once before the least recently used
+/// unpinned one is stopped. Lowering it stops tracers right away, though
+/// pinned ones are kept even past the cap. Returns the cap now in effect.
+#[tauri::command]
+fn set_max_tracers(
+    n: usize,
+    config: State<SharedConfig>,
+    tracer_state: State<SharedTracer>,
+    settings: State<SharedSettings>,
+) -> Result<usize, FlowlensError> {
+    if n < 1 {
+        return Err(FlowlensError::InvalidRequest {
+            message: "max tracers must be at least 1".to_string(),
+        });
+    }
+    settings
+        .lock()
+        .unwrap()
+        .update(|saved| saved.max_tracers = Some(n))?;
+    config.lock().unwrap().max_tracers = Some(n);
+    let evicted = evict_lru(&mut tracer_state.map.lock().unwrap(), n);
+    if !evicted.is_empty() {
+        println!("[flowlens] stopped {} tracer(s) to fit the new cap", evicted.len());
+    }
+    Ok(n)
+}
+
+/// How many tracers may run at once.
+#[tauri::command]
+fn get_max_tracers(config: State<SharedConfig>) -> usize {
+    config.lock().unwrap().max_tracers()
+}
+
+/// Tracer backends the repo's interpreter supports, least preferred first,
+/// with the one used when neither the request nor the config picks one.
+#[tauri::command]
+fn available_backends(config: State<SharedConfig>) -> Value {
+    let config = config.lock().unwrap().clone();
+    let version = config.python().version;
+    json!({
+        "python_version": version.map(|(major, minor)| format!("{}.{}", major, minor)),
+        "backends": TracerBackend::available(version),
+        "default": TracerBackend::default_for(version),
+        "configured": config.tracer_backend,
+    })
+}
+
+/// Set the tracer backend for requests that don't pick one, or `None` for the
+/// interpreter's default. Applies from the next spawned tracer.
+#[tauri::command]
+fn set_tracer_backend(backend: Option<String>, config: State<SharedConfig>) -> Result<(), FlowlensError> {
+    println!("[flowlens] set_tracer_backend: {:?}", backend);
+    let mut config = config.lock().unwrap();
+    if let Some(name) = &backend {
+        TracerBackend::parse(name, config.python().version)?;
+    }
+    config.tracer_backend = backend;
+    Ok(())
+}
+
+/// Set how many times a tracer that exits on spawn is restarted.
+#[tauri::command]
+fn set_spawn_retries(retries: u32, config: State<SharedConfig>) {
+    println!("[flowlens] set_spawn_retries: {}", retries);
+    config.lock().unwrap().spawn_retries = Some(retries);
+}
+
+/// Directories (relative to the repo) Python should import from before the
+/// repo root, e.g. `["src"]` for a src layout. Replaces the previous list and
+/// is reset when the repo changes.
+#[tauri::command]
+fn set_sys_path(paths: Vec<String>, config: State<SharedConfig>) -> Result<Vec<String>, FlowlensError> {
+    println!("[flowlens] set_sys_path: {:?}", paths);
+    let mut config = config.lock().unwrap();
+    config.sys_path = resolve_sys_path(&config.repo(), &paths)?;
+    Ok(config.sys_path.clone())
+}
+
+/// Choose where traces started without a `stop_line` or breakpoints first pause.
+#[tauri::command]
+fn set_default_stop(default_stop: DefaultStop, config: State<SharedConfig>) {
+    println!("[flowlens] set_default_stop: {:?}", default_stop);
+    config.lock().unwrap().default_stop = default_stop;
+}
+
+/// Set how many source lines either side of the paused line each event carries
+/// as `context: {start, lines}`, 0 for none. Applies to tracers spawned from
+/// now on. Returns the window now in effect.
+#[tauri::command]
+fn set_context_lines(lines: usize, config: State<SharedConfig>) -> Result<usize, FlowlensError> {
+    println!("[flowlens] set_context_lines: {}", lines);
+    if lines > MAX_CONTEXT_LINES {
+        return Err(FlowlensError::InvalidRequest {
+            message: format!("context is limited to {} lines either side", MAX_CONTEXT_LINES),
+        });
+    }
+    config.lock().unwrap().context_lines = Some(lines);
+    Ok(lines)
+}
+
+/// Choose the channel tracers spawned from now on send events over.
+#[tauri::command]
+fn set_events_channel(channel: EventChannel, config: State<SharedConfig>) {
+    println!("[flowlens] set_events_channel: {:?}", channel);
+    config.lock().unwrap().events_on = channel;
+}
+
+/// Which optional features work in this build and setup, so the UI can hide
+/// what would only error. Runs no Python and changes nothing.
+#[tauri::command]
+fn capabilities(config: State<SharedConfig>) -> BTreeMap<&'static str, bool> {
+    let config = config.lock().unwrap().clone();
+    // Also false when git is not installed
+    let git_repo = !config.demo && head_commit(&config.repo()).is_some();
+
+    BTreeMap::from([
+        ("tools", missing_tools(&config.tools_dir()).is_empty()),
+        ("python", config.checked_python().is_ok()),
+        ("worktrees", git_repo),
+        ("changed_functions", git_repo || config.demo),
+        ("function_diff", git_repo),
+        ("async_functions", true),
+        ("generators", true),
+        ("breakpoints", true),
+        ("prewarm", true),
+        ("events_channel", true),
+        ("streaming", true),
+        ("recording", false),
+        ("gzip", false),
+    ])
+}
+
+/// Each Python tool with its resolved path, whether it exists, and every
+/// argument the app may pass it. For tools that exist, `unknown_args` lists
+/// flags the app passes that the script's `--help` doesn't mention, which
+/// catches a script and the Rust side drifting apart.
+#[tauri::command]
+fn list_tools(config: State<SharedConfig>) -> Vec<Value> {
+    let config = config.lock().unwrap().clone();
+    let python = config.python().path;
+    TOOLS
+        .iter()
+        .map(|tool: &ToolSpec| {
+            let path = config.script_path(tool.script);
+            let exists = path.is_file();
+            let unknown_args: Option<Vec<&str>> = exists.then(|| {
+                let help = output_logged(config.python_command(&python).arg(&path).arg("--help"))
+                    .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+                    .unwrap_or_default();
+                tool.args.iter().map(|arg| arg.flag).filter(|flag| !help.contains(flag)).collect()
+            });
+            json!({
+                "script": tool.script,
+                "purpose": tool.purpose,
+                "path": path,
+                "exists": exists,
+                "args": tool.args,
+                "unknown_args": unknown_args,
+            })
+        })
+        .collect()
+}
+
+/// Keep the file header and only those hunks of a unified diff whose new-side
+/// range overlaps `start..=end`.
+fn filter_diff_to_range(diff: &str, start: u32, end: u32) -> String {
+    let mut header = String::new();
+    let mut kept = String::new();
+    let mut keep_hunk = false;
+    let mut in_hunks = false;
+
+    for line in diff.lines() {
+        if let Some(range) = line.strip_prefix("@@ ") {
+            in_hunks = true;
+            // "@@ -a,b +c,d @@ context": the new side is "+c,d" (",d" defaults to 1)
+            let new_side = range.split_whitespace().find_map(|part| part.strip_prefix('+'));
+            keep_hunk = new_side.is_some_and(|new_side| {
+                let mut nums = new_side.splitn(2, ',').map(|n| n.parse::<u32>().unwrap_or(0));
+                let first = nums.next().unwrap_or(0);
+                let count = nums.next().unwrap_or(1);
+                let last = first + count.saturating_sub(1);
+                first <= end && last >= start
+            });
+        }
+        let target = if !in_hunks {
+            &mut header
+        } else if keep_hunk {
+            &mut kept
+        } else {
+            continue;
+        };
+        target.push_str(line);
+        target.push('\n');
+    }
+
+    if kept.is_empty() {
+        return String::new();
+    }
+    header + &kept
+}
+
+/// Unified diff of the hunks touching one function, against `base_ref` or the index.
+#[tauri::command]
+fn get_function_diff(
+    entry_full_id: String,
+    base_ref: Option<String>,
+    config: State<SharedConfig>,
+) -> Result<Value, FlowlensError> {
+    println!("[flowlens] get_function_diff: {} (base={:?})", entry_full_id, base_ref);
+
+    let config = config.lock().unwrap().clone();
+    let repo = config.repo();
+    let signature = run_signature(&config, &entry_full_id, &[])?;
+    if let Some(err) = signature.error {
+        return Err(FlowlensError::InvalidRequest {
+            message: format!("Could not locate {}: {}", entry_full_id, err),
+        });
+    }
+    let (Some(file), Some(start_line), Some(end_line)) = (signature.file, signature.start_line, signature.end_line) else {
+        return Err(FlowlensError::InvalidRequest {
+            message: format!("No source span available for {}", entry_full_id),
+        });
+    };
+    if config.demo {
+        // The demo has no git history, so nothing ever differs
+        return Ok(json!({
+            "entry_full_id": entry_full_id,
+            "file": file,
+            "start_line": start_line,
+            "end_line": end_line,
+            "diff": ""
+        }));
+    }
+
+    let mut cmd = Command::new("git");
+    cmd.arg("-C").arg(&repo).arg("diff");
+    if let Some(base_ref) = base_ref.as_deref().filter(|r| !r.is_empty()) {
+        if base_ref.starts_with('-') {
+            return Err(FlowlensError::InvalidRequest {
+                message: format!("invalid base ref: {}", base_ref),
+            });
+        }
+        cmd.arg(base_ref);
+    }
+    let output = checked_output("git diff", cmd.arg("--").arg(&file))?;
+
+    let diff = filter_diff_to_range(&String::from_utf8_lossy(&output.stdout), start_line, end_line);
+    Ok(json!({
+        "entry_full_id": entry_full_id,
+        "file": file,
+        "start_line": start_line,
+        "end_line": end_line,
+        "diff": diff
+    }))
+}
+
+/// Upper bound on children returned when expanding one variable.
+const MAX_EXPAND_CHILDREN: usize = 100;
+
+/// The tracer for `entry_full_id`, marked as just used. Lock it with
+/// `lock_active`, which also rejects one that is only prewarmed.
+fn active_tracer(tracers: &SharedTracer, entry_full_id: &str) -> Result<Arc<TracerSlot>, FlowlensError> {
+    let slot = tracer_slot(tracers, &normalize_entry_id(entry_full_id)).ok_or_else(|| FlowlensError::NoActiveTracer {
+        entry_full_id: entry_full_id.to_string(),
+    })?;
+    slot.touch();
+    Ok(slot)
+}
+
+/// Run `f` on the active tracer for `entry_full_id`, killing it if it times
+/// out, as `get_tracer_data` does (see `run_on_slot`).
+fn with_active_tracer<T>(
+    tracers: &SharedTracer,
+    entry_full_id: &str,
+    f: impl FnOnce(&mut Tracer) -> Result<T, FlowlensError>,
+) -> Result<T, FlowlensError> {
+    let slot = active_tracer(tracers, entry_full_id)?;
+    let tracer = slot.lock_active(entry_full_id)?;
+    run_on_slot(tracers, &normalize_entry_id(entry_full_id), &slot, tracer, f)
+}
+
+/// Children of a local container (dict/list/object) at `path`, e.g.
+/// `["config", "db", "hosts", "0"]`, resolved in the paused frame.
+#[tauri::command]
+fn expand_variable(
+    entry_full_id: String,
+    path: Vec<String>,
+    tracer_state: State<SharedTracer>,
+) -> Result<Value, FlowlensError> {
+    println!("[Rust] expand_variable {} {:?}", entry_full_id, path);
+    if path.is_empty() {
+        return Err(FlowlensError::InvalidRequest {
+            message: "variable path is empty".to_string(),
+        });
+    }
+
+    let command = json!({ "cmd": "expand", "path": path, "limit": MAX_EXPAND_CHILDREN });
+    with_active_tracer(&tracer_state, &entry_full_id, |tracer| {
+        tracer.send_line(command.to_string())?;
+        tracer.read_event()
+    })
+}
+
+/// Replace the breakpoints of the live tracer for `entry_full_id` without
+/// restarting it, so stepping state is kept. Checked like the breakpoints of
+/// a trace request; an empty list clears them. Answers with the `breakpoints`
+/// now active.
+#[tauri::command]
+fn update_breakpoints(
+    entry_full_id: String,
+    breakpoints: Vec<Breakpoint>,
+    tracer_state: State<SharedTracer>,
+) -> Result<Value, FlowlensError> {
+    println!("[Rust] update_breakpoints {} ({} breakpoints)", entry_full_id, breakpoints.len());
+
+    with_active_tracer(&tracer_state, &entry_full_id, |tracer| {
+        // Against the tracer's own root, which is the worktree when tracing `at_commit`
+        let spec = breakpoints_arg(tracer.paths.root(), &breakpoints)?;
+        let command = json!({ "cmd": "breakpoints", "spec": spec });
+        tracer.send_line(command.to_string())?;
+        let event = tracer.read_event()?;
+        tracer.breakpoints = breakpoints;
+        Ok(event)
+    })
+}
+
+/// Upper bound on items returned by one `slice_variable` call.
+const MAX_SLICE_COUNT: usize = 1000;
+
+/// Items `start..start + count` of the list, tuple, set or dict at `path`,
+/// plus its `total` length, so huge collections can be paged through. `count`
+/// is capped at `MAX_SLICE_COUNT`.
+#[tauri::command]
+fn slice_variable(
+    entry_full_id: String,
+    path: Vec<String>,
+    start: usize,
+    count: usize,
+    tracer_state: State<SharedTracer>,
+) -> Result<Value, FlowlensError> {
+    println!("[Rust] slice_variable {} {:?} [{}; {}]", entry_full_id, path, start, count);
+    if path.is_empty() {
+        return Err(FlowlensError::InvalidRequest {
+            message: "variable path is empty".to_string(),
+        });
+    }
+
+    let command = json!({ "cmd": "slice", "path": path, "start": start, "count": count.min(MAX_SLICE_COUNT) });
+    with_active_tracer(&tracer_state, &entry_full_id, |tracer| {
+        tracer.send_line(command.to_string())?;
+        tracer.read_event()
+    })
+}
+
+/// Final result of the traced function, once stepping has run past its end.
+#[tauri::command]
+fn get_return_value(entry_full_id: String, tracer_state: State<SharedTracer>) -> Result<Value, FlowlensError> {
+    println!("[Rust] get_return_value {}", entry_full_id);
+    let slot = active_tracer(&tracer_state, &entry_full_id)?;
+    let tracer = slot.lock_active(&entry_full_id)?;
+    tracer
+        .return_value
+        .clone()
+        .ok_or(FlowlensError::NoReturnYet { entry_full_id })
+}
+
+/// Exempt the tracer for `entry_full_id` from eviction when another flow needs
+/// room, or make it evictable again. Stopping it through `close_tracer` or
+/// `emergency_stop` still works.
+#[tauri::command]
+fn pin_tracer(entry_full_id: String, pinned: bool, tracer_state: State<SharedTracer>) -> Result<(), FlowlensError> {
+    println!("[Rust] pin_tracer {} {}", entry_full_id, pinned);
+    let slot = active_tracer(&tracer_state, &entry_full_id)?;
+    drop(slot.lock_active(&entry_full_id)?);
+    slot.pinned.store(pinned, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Every running tracer, most recently used first. `tracer_id` is the
+/// `entry_full_id` other commands take to reach it, which differs for an
+/// `at_commit` trace. A tracer in the middle of a step is listed once the
+/// step is answered.
+#[tauri::command]
+fn list_active_tracers(tracer_state: State<SharedTracer>) -> Vec<Value> {
+    let mut tracers = tracer_slots(&tracer_state);
+    tracers.sort_by_key(|(_, slot)| std::cmp::Reverse(slot.last_used()));
+    tracers
+        .into_iter()
+        .map(|(key, slot)| {
+            let tracer = slot.tracer.lock().unwrap();
+            json!({
+                "tracer_id": key,
+                "entry_full_id": tracer.current_flow,
+                "at_commit": tracer.at_commit,
+                "prewarmed": tracer.prewarmed,
+                "pinned": slot.pinned.load(Ordering::SeqCst),
+            })
+        })
+        .collect()
+}
+
+/// Stop the tracer for `entry_full_id` (at `at_commit`, HEAD when omitted),
+/// prewarmed or not, pinned or not, leaving every other tracer running.
+#[tauri::command]
+fn close_tracer(
+    entry_full_id: String,
+    at_commit: Option<String>,
+    tracer_state: State<SharedTracer>,
+) -> Result<(), FlowlensError> {
+    println!("[Rust] close_tracer {} (at_commit={:?})", entry_full_id, at_commit);
+    // Dropping it stops the process, once a command still using it is done
+    let removed = tracer_state.map.lock().unwrap().remove(&tracer_key(&entry_full_id, at_commit.as_deref()));
+    match removed {
+        Some(_) => Ok(()),
+        None => Err(FlowlensError::NoActiveTracer { entry_full_id }),
+    }
+}
+
+/// What the tracer for `entry_full_id` wrote besides events: `lines` is stray
+/// output on the events channel (e.g. prints from the traced code when events
+/// go over stdout), `diagnostics` is the other channel (Python's warnings and
+/// tracebacks when events go over stdout). Each keeps the newest
+/// `tracer_log_lines`; `truncated` says older `lines` were dropped.
+#[tauri::command]
+fn get_tracer_logs(entry_full_id: String, tracer_state: State<SharedTracer>) -> Result<Value, FlowlensError> {
+    let slot = active_tracer(&tracer_state, &entry_full_id)?;
+    let tracer = slot.lock_active(&entry_full_id)?;
+    let diagnostics = tracer.diagnostics.lock().unwrap().clone();
+    Ok(json!({ "lines": tracer.log, "truncated": tracer.log_truncated, "diagnostics": diagnostics }))
+}
+
+/// Emit each new output line of the tracer for `entry_full_id` as a
+/// `tracer-log` event (`{entry_full_id, channel, line, request_id}`) as it is
+/// read, until that tracer is replaced or stopped. `channel` is `events` for
+/// stray output on the events channel and `diagnostics` for the other one,
+/// where tracebacks and the traced code's prints go by default. Returns the
+/// lines buffered so far, like `get_tracer_logs`, so the console can start
+/// from them.
+#[tauri::command]
+fn tail_tracer_logs(
+    entry_full_id: String,
+    request_id: Option<String>,
+    app: AppHandle,
+    tracer_state: State<SharedTracer>,
+) -> Result<Value, FlowlensError> {
+    println!("[flowlens] tail_tracer_logs {} (request_id={:?})", entry_full_id, request_id);
+    let slot = active_tracer(&tracer_state, &entry_full_id)?;
+    let tracer = slot.lock_active(&entry_full_id)?;
+    let flow = tracer.current_flow.clone();
+    let sink: LogSink = Arc::new(move |channel, line| {
+        let payload = json!({ "entry_full_id": flow, "channel": channel, "line": line, "request_id": request_id });
+        if let Err(e) = app.emit("tracer-log", payload) {
+            println!("[flowlens] failed to emit tracer-log: {}", e);
+        }
+    });
+    // Taken first, as the diagnostics reader does, so no line falls between snapshot and sink
+    let diagnostics = tracer.diagnostics.lock().unwrap();
+    *tracer.log_tail.lock().unwrap() = Some(sink);
+    Ok(json!({ "lines": tracer.log, "truncated": tracer.log_truncated, "diagnostics": *diagnostics }))
+}
+
+/// Hide locals from the events of the tracer for `entry_full_id`, from the
+/// next step on: names in `exclude` always, and with `include_only` every name
+/// not listed. Kept across steps; a respawned tracer starts unfiltered.
+/// Summaries and dumps still see every local.
+#[tauri::command]
+fn set_local_filter(
+    entry_full_id: String,
+    exclude: Vec<String>,
+    include_only: Option<Vec<String>>,
+    tracer_state: State<SharedTracer>,
+) -> Result<(), FlowlensError> {
+    println!("[flowlens] set_local_filter {}: exclude={:?} include_only={:?}", entry_full_id, exclude, include_only);
+    let slot = active_tracer(&tracer_state, &entry_full_id)?;
+    let mut tracer = slot.lock_active(&entry_full_id)?;
+    tracer.local_filter = LocalFilter { exclude, include_only };
+    Ok(())
+}
+
+/// Set how many stray output lines each tracer spawned from now on keeps.
+#[tauri::command]
+fn set_tracer_log_lines(lines: usize, config: State<SharedConfig>) -> Result<(), FlowlensError> {
+    println!("[flowlens] set_tracer_log_lines: {}", lines);
+    if lines == 0 {
+        return Err(FlowlensError::InvalidRequest {
+            message: "tracer_log_lines must be at least 1".to_string(),
+        });
+    }
+    config.lock().unwrap().tracer_log_lines = Some(lines);
+    Ok(())
+}
+
+/// Names of the paused frame's locals, without their values.
+#[derive(Serialize, Debug)]
+struct LocalsSummary {
+    count: usize,
+    names: Vec<String>,
+    /// Locals that are new or hold a different value than at the previous step;
+    /// empty on the first step
+    changed_since_last: Vec<String>,
+}
+
+/// Cheap overview of the current step's locals, e.g. for a status bar while
+/// the full inspector is collapsed.
+#[tauri::command]
+fn locals_summary(entry_full_id: String, tracer_state: State<SharedTracer>) -> Result<LocalsSummary, FlowlensError> {
+    println!("[Rust] locals_summary {}", entry_full_id);
+    let slot = active_tracer(&tracer_state, &entry_full_id)?;
+    let tracer = slot.lock_active(&entry_full_id)?;
+
+    let empty = Map::new();
+    let current = tracer.current_locals.as_ref().unwrap_or(&empty);
+    let changed_since_last = match &tracer.previous_locals {
+        Some(previous) => current
+            .iter()
+            .filter(|(name, value)| previous.get(*name) != Some(*value))
+            .map(|(name, _)| name.clone())
+            .collect(),
+        None => Vec::new(),
+    };
+
+    Ok(LocalsSummary {
+        count: current.len(),
+        names: current.keys().cloned().collect(),
+        changed_since_last,
+    })
+}
+
+/// `path` as a file to write: absolute, not a directory, in a directory that exists.
+fn output_path(path: &str) -> Result<PathBuf, FlowlensError> {
+    let out = PathBuf::from(path.trim());
+    let invalid = |message: String| FlowlensError::InvalidRequest { message };
+    if !out.is_absolute() {
+        return Err(invalid(format!("{} is not an absolute path", out.display())));
+    }
+    if out.is_dir() {
+        return Err(invalid(format!("{} is a directory", out.display())));
+    }
+    if !out.parent().is_some_and(Path::is_dir) {
+        return Err(invalid(format!("the directory of {} does not exist", out.display())));
+    }
+    Ok(out)
+}
+
+/// Write the steps traced so far for `entry_full_id` to `path` as JSON
+/// columns, one array per field with one entry per step (`lines`,
+/// `functions`, `files`, `phases`, `step_ms`), ready for a dataframe. Works
+/// the same on a trace that has completed.
+#[tauri::command]
+fn export_trace_columnar(entry_full_id: String, path: String, tracer_state: State<SharedTracer>) -> Result<Value, FlowlensError> {
+    println!("[Rust] export_trace_columnar {} -> {}", entry_full_id, path);
+    let out = output_path(&path)?;
+
+    let columns = {
+        let slot = active_tracer(&tracer_state, &entry_full_id)?;
+        let tracer = slot.lock_active(&entry_full_id)?;
+        let history = &tracer.history;
+        json!({
+            "entry_full_id": entry_full_id,
+            "steps": history.len(),
+            "lines": history.iter().map(|s| s.line).collect::<Vec<_>>(),
+            "functions": history.iter().map(|s| s.function.as_str()).collect::<Vec<_>>(),
+            "files": history.iter().map(|s| s.file.as_str()).collect::<Vec<_>>(),
+            "phases": history.iter().map(|s| s.phase).collect::<Vec<_>>(),
+            "step_ms": history.iter().map(|s| s.step_ms).collect::<Vec<_>>(),
+        })
+    };
+
+    let text = serde_json::to_string(&columns).map_err(|e| FlowlensError::io(&out, e))?;
+    std::fs::write(&out, text).map_err(|e| FlowlensError::io(&out, e))?;
+    Ok(json!({ "path": out, "steps": columns["steps"] }))
+}
+
+/// Write the current step's locals, untruncated, to `out_path` as pretty JSON
+/// along with the entry id, line and time (Unix seconds), e.g. to attach to a
+/// ticket. `out_path` must be absolute and its directory must exist. Values
+/// are as the tracer serialized them: containers in full, other objects as `str()`.
+#[tauri::command]
+fn dump_locals(entry_full_id: String, out_path: String, tracer_state: State<SharedTracer>) -> Result<Value, FlowlensError> {
+    println!("[Rust] dump_locals {} -> {}", entry_full_id, out_path);
+    let out = output_path(&out_path)?;
+
+    let snapshot = {
+        let slot = active_tracer(&tracer_state, &entry_full_id)?;
+        let tracer = slot.lock_active(&entry_full_id)?;
+        json!({
+            "entry_full_id": entry_full_id,
+            "line": tracer.current_line,
+            "timestamp": SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            "locals": tracer.current_locals.clone().unwrap_or_default(),
+        })
+    };
+
+    let text = serde_json::to_string_pretty(&snapshot).map_err(|e| FlowlensError::io(&out, e))?;
+    std::fs::write(&out, text).map_err(|e| FlowlensError::io(&out, e))?;
+    Ok(json!({ "path": out, "count": snapshot["locals"].as_object().map_or(0, Map::len) }))
+}
+
+/// The event the trace is paused at, as last returned, without advancing,
+/// e.g. to restore the UI after a reload.
+#[tauri::command]
+fn refresh_current_event(entry_full_id: String, tracer_state: State<SharedTracer>) -> Result<Value, FlowlensError> {
+    let slot = active_tracer(&tracer_state, &entry_full_id)?;
+    let tracer = slot.lock_active(&entry_full_id)?;
+    tracer
+        .last_event
+        .clone()
+        .ok_or(FlowlensError::NoCurrentEvent { entry_full_id })
+}
+
+#[derive(Deserialize, Default)]
+struct CfgLine {
+    #[serde(default)]
+    successors: Vec<Value>,
+    #[serde(default)]
+    calls: Vec<Value>,
+}
+
+#[derive(Deserialize)]
+struct CfgResult {
+    #[serde(default)]
+    lines: HashMap<String, CfgLine>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// Where the paused trace could stop next: the lines statically reachable
+/// from the current one (`{line, kind}`, kind being next, branch, loop_body,
+/// loop_exit, loop_back or handler) and the calls it makes, for suggesting
+/// "step to" targets. Empty when the current line isn't in the function body.
+#[tauri::command]
+fn next_step_targets(
+    entry_full_id: String,
+    tracer_state: State<SharedTracer>,
+    config: State<SharedConfig>,
+) -> Result<Value, FlowlensError> {
+    let line = {
+        let slot = active_tracer(&tracer_state, &entry_full_id)?;
+        let tracer = slot.lock_active(&entry_full_id)?;
+        tracer.current_line.ok_or_else(|| FlowlensError::NoCurrentEvent {
+            entry_full_id: entry_full_id.clone(),
+        })?
+    };
+
+    let config = config.lock().unwrap().clone();
+    let result: CfgResult = run_tracer_query(&config, &entry_full_id, &["--cfg"])?;
+    if let Some(err) = result.error {
+        return Err(FlowlensError::InvalidRequest {
+            message: format!("Could not analyse {}: {}", entry_full_id, err),
+        });
+    }
+    let mut lines = result.lines;
+    let current = lines.remove(&line.to_string()).unwrap_or_default();
+    Ok(json!({
+        "line": line,
+        "targets": current.successors,
+        "calls": current.calls,
+    }))
+}
+
+/// Whether `file:line` is one of the frames of the paused trace, checked
+/// against the cached event's `stack` (outermost first) or, for events
+/// without one, just the paused frame. `file` may be absolute or
+/// repo-relative. `frame_index` indexes the stack; with no current event the
+/// line is simply inactive.
+#[tauri::command]
+fn is_line_active(entry_full_id: String, file: String, line: u32, tracer_state: State<SharedTracer>) -> Result<Value, FlowlensError> {
+    let slot = active_tracer(&tracer_state, &entry_full_id)?;
+    let tracer = slot.lock_active(&entry_full_id)?;
+    // Events carry repo-relative paths, and absolute ones for files outside the repo
+    let file = tracer.paths.relative(&file).unwrap_or(file);
+    let matches_file = |name: &str| name == file || name == file.trim_start_matches('/');
+
+    let frames = match &tracer.last_event {
+        Some(event) => match event.get("stack").and_then(Value::as_array) {
+            Some(stack) => stack.iter().collect(),
+            None => vec![event],
+        },
+        None => Vec::new(),
+    };
+    let frame_index = frames.iter().position(|frame| {
+        frame.get("filename").and_then(Value::as_str).is_some_and(matches_file)
+            && frame.get("line").and_then(Value::as_u64) == Some(u64::from(line))
+    });
+    Ok(json!({ "active": frame_index.is_some(), "frame_index": frame_index }))
+}
+
+/// The exact last line the tracer wrote on its events channel, for debugging
+/// protocol problems that error messages truncate. Lines longer than the
+/// payload byte budget are cut, with `truncated` set.
+#[tauri::command]
+fn last_raw_line(entry_full_id: String, tracer_state: State<SharedTracer>) -> Result<Value, FlowlensError> {
+    let slot = active_tracer(&tracer_state, &entry_full_id)?;
+    let tracer = slot.lock_active(&entry_full_id)?;
+    Ok(json!({
+        "line": tracer.last_raw_line,
+        "len": tracer.last_raw_len,
+        "truncated": tracer.last_raw_line.len() < tracer.last_raw_len,
+    }))
+}
+
+/// 64-bit FNV-1a of `bytes`. Fixed by its spec, unlike `std`'s hashers, so
+/// digests can be compared across builds and toolchains.
+fn fnv1a64(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Hex digest of the (function, line) sequence traced so far, ignoring values.
+/// Two runs of the same trace with different fingerprints took different paths.
+/// The digest is FNV-1a over the sequence as compact JSON (`[["fn",3],...]`).
+#[tauri::command]
+fn trace_fingerprint(entry_full_id: String, tracer_state: State<SharedTracer>) -> Result<Value, FlowlensError> {
+    let slot = active_tracer(&tracer_state, &entry_full_id)?;
+    let tracer = slot.lock_active(&entry_full_id)?;
+
+    let path: Vec<(&str, u64)> = tracer.history.iter().map(|step| (step.function.as_str(), step.line)).collect();
+    let canonical = serde_json::to_vec(&path).expect("(function, line) pairs always serialize");
+    Ok(json!({
+        "fingerprint": format!("{:016x}", fnv1a64(&canonical)),
+        "steps": tracer.history.len(),
+    }))
+}
+
+/// `get_tracer.py` processes left over from earlier sessions or crashes: every
+/// process running this app's tracer script except the live tracers.
+#[tauri::command]
+fn find_orphans(tracer_state: State<SharedTracer>, config: State<SharedConfig>) -> Vec<TracerProcess> {
+    let script = config.lock().unwrap().script_path(tools::TRACER);
+    let tracked: Vec<u32> = tracer_slots(&tracer_state).iter().map(|(_, slot)| slot.pid).collect();
+    orphans::find_untracked(&script, &tracked)
+}
+
+/// Kill everything `find_orphans` reports.
+#[tauri::command]
+fn reap_orphans(tracer_state: State<SharedTracer>, config: State<SharedConfig>) -> Value {
+    let (killed, failed): (Vec<u32>, Vec<u32>) = find_orphans(tracer_state, config)
+        .into_iter()
+        .map(|orphan| orphan.pid)
+        .partition(|pid| orphans::kill(*pid));
+    println!("[flowlens] reap_orphans: killed {:?}, failed {:?}", killed, failed);
+    json!({ "killed": killed, "failed": failed })
+}
+
+/// Kill every tracer process straight away, without waiting on the commands
+/// using them, then take the tracers out of the map and reap them. A command
+/// blocked on a step fails as soon as its process is gone, freeing the
+/// tracer lock waited on here. Returns how many tracers there were.
+fn stop_all_tracers(tracers: &SharedTracer) -> usize {
+    process::kill_tracers();
+    let slots = take_all_tracers(tracers);
+    for slot in &slots {
+        slot.tracer.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).kill();
+    }
+    slots.len()
+}
+
+/// Escape hatch for a wedged session: kill every tracer and running tool,
+/// stop trace streams and the repo watcher, drop cached analysis and tell
+/// the UI. Safe to call at any time, including while a step hangs, after a
+/// panic poisoned a lock or when the processes are already gone. Unlike
+/// `shutdown`, later commands still work.
+#[tauri::command]
+fn emergency_stop(app: AppHandle, tracer_state: State<SharedTracer>) -> Value {
+    println!("[flowlens] emergency_stop");
+
+    // First, so nothing below waits on a hung step
+    let killed = stop_all_tracers(&tracer_state);
+
+    let streams_stopped = app.state::<TraceStreams>().stop_all();
+    // Out of its lock before joining: a change callback in progress locks the tracers
+    let watcher = app.state::<SharedRepoWatcher>().lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+    let watcher_stopped = watcher.map(RepoWatcher::stop).is_some();
+
+    let tools_killed = process::running_pids().into_iter().filter(|pid| orphans::kill(*pid)).count();
+
+    app.state::<SharedCallersCache>().lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clear();
+    app.state::<SharedCalleesCache>().lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clear();
+    app.state::<SharedCoverage>().lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clear();
+    app.state::<SharedChangedFunctions>().lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clear();
+
+    let summary = json!({
+        "tracers_killed": killed,
+        "tools_killed": tools_killed,
+        "streams_stopped": streams_stopped,
+        "watcher_stopped": watcher_stopped,
+    });
+    if let Err(e) = app.emit("emergency-stopped", summary.clone()) {
+        println!("[flowlens] failed to emit emergency-stopped: {}", e);
+    }
+    summary
+}
+
+/// Set by `shutdown` and cleared by `resume`; every other command in between
+/// fails with `ShuttingDown`.
+#[derive(Default)]
+struct ShutdownFlag(AtomicBool);
+
+/// Tear down the app's background work without quitting, e.g. to close a
+/// project or end an integration test: stop trace streams, the repo watcher
+/// and the tracers, wait for their threads, and flush output. Every later
+/// command fails with `ShuttingDown` until `resume`. Returns what was stopped.
+#[tauri::command]
+fn shutdown(
+    flag: State<ShutdownFlag>,
+    tracer_state: State<SharedTracer>,
+    watcher: State<SharedRepoWatcher>,
+    streams: State<TraceStreams>,
+) -> Value {
+    println!("[flowlens] shutdown");
+    // First, so no command starts new work while the rest is torn down
+    flag.0.store(true, Ordering::SeqCst);
+    // Before the streams and the watcher, so neither waits on a hung step
+    let killed = stop_all_tracers(&tracer_state);
+    let streams_stopped = streams.stop_all();
+
+    // Out of its lock before joining: a change callback in progress locks the tracers
+    let watcher = watcher.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+    let watcher_stopped = watcher.map(RepoWatcher::stop).is_some();
+
+    let _ = std::io::stdout().flush();
+    json!({ "tracers_killed": killed, "watcher_stopped": watcher_stopped, "streams_stopped": streams_stopped })
+}
+
+/// Undo `shutdown`, e.g. when closing the project was cancelled or a test
+/// starts over. Nothing is restarted: tracers spawn again on the next trace
+/// request and the watcher with `watch_repo`. Returns whether the app was shut down.
+#[tauri::command]
+fn resume(flag: State<ShutdownFlag>) -> bool {
+    println!("[flowlens] resume");
+    flag.0.swap(false, Ordering::SeqCst)
+}
+
+/// Adjust the size budget applied to trace events from now on.
+#[tauri::command]
+fn set_payload_limits(limits: PayloadLimits, config: State<SharedConfig>) -> Result<(), FlowlensError> {
+    println!("[flowlens] set_payload_limits: {:?}", limits);
+    if limits.max_field_len == 0 || limits.max_payload_bytes == 0 {
+        return Err(FlowlensError::InvalidRequest {
+            message: "payload limits must be greater than zero".to_string(),
+        });
+    }
+    config.lock().unwrap().payload_limits = limits;
+    Ok(())
+}
+
+#[tauri::command]
+fn recent_flows(limit: usize, recent: State<SharedRecentFlows>) -> Vec<RecentFlow> {
+    recent.lock().unwrap().newest(limit)
+}
+
+/// Where the app keeps its files on this machine.
+#[derive(Serialize, Debug)]
+struct AppPaths {
+    /// Settings and `recent_flows.json`
+    config_dir: PathBuf,
+    /// Recordings and other data that can be regenerated
+    cache_dir: PathBuf,
+    log_dir: PathBuf,
+    /// Where `get_tracer.py` writes its per-run log files
+    tracer_log_dir: PathBuf,
+}
+
+/// The app's config, cache and log directories, created if missing, for
+/// "where are my files" questions in bug reports.
+#[tauri::command]
+fn app_paths(app: AppHandle, config: State<SharedConfig>) -> Result<AppPaths, FlowlensError> {
+    let resolver = app.path();
+    let paths = AppPaths {
+        config_dir: resolver.app_config_dir().map_err(|e| FlowlensError::io(Path::new("app config dir"), e))?,
+        cache_dir: resolver.app_cache_dir().map_err(|e| FlowlensError::io(Path::new("app cache dir"), e))?,
+        log_dir: resolver.app_log_dir().map_err(|e| FlowlensError::io(Path::new("app log dir"), e))?,
+        tracer_log_dir: {
+            let config = config.lock().unwrap();
+            config.tracer_log_dir.clone().unwrap_or_else(|| config.tools_dir().join("..").join("logs"))
+        },
+    };
+    for dir in [&paths.config_dir, &paths.cache_dir, &paths.log_dir] {
+        std::fs::create_dir_all(dir).map_err(|e| FlowlensError::io(dir, e))?;
+    }
+    Ok(paths)
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    println!("[flowlens] run: starting tauri builder");
+    // Boxed to give the generated closure a type, so the handler below can wrap it
+    let handler: Box<dyn Fn(tauri::ipc::Invoke) -> bool + Send + Sync> = Box::new(tauri::generate_handler![greet, set_repo_path, get_flows, get_file_tree, get_tracer_data, get_function_signature, recent_flows, set_events_channel, get_function_diff, emergency_stop, function_metrics, expand_variable, set_payload_limits, run_to_function, list_changed_functions, use_demo_repo, set_tools_dir, run_to_end, get_callees, get_callers, prewarm_tracer, get_return_value, set_env_isolation, set_spawn_retries, locals_summary, pin_tracer, list_active_tracers, capabilities, get_tracer_logs, app_paths, set_tracer_log_lines, dump_locals, refresh_python, save_breakpoint_preset, list_breakpoint_presets, apply_breakpoint_preset, slice_variable, trace_fingerprint, set_default_stop, refresh_current_event, find_orphans, reap_orphans, last_raw_line, set_sys_path, next_step_targets, parse_entry_id, finish, is_line_active, tail_tracer_logs, profile_run, collect_until, set_local_filter, check_importable, update_breakpoints, last_failure, trace_next_changed, estimate_trace, list_tools, export_trace_columnar, set_read_timeout, get_read_timeout, file_coverage, annotated_source, watch_repo, unwatch_repo, available_backends, set_tracer_backend, call_graph, tracer_queue_depth, step_over_with_diff, set_max_tracers, get_max_tracers, set_context_lines, shutdown, resume, close_tracer, start_trace_stream, stop_trace_stream, check_python]);
+    tauri::Builder::default()
+        .manage(SharedTracer::default())  // register the shared tracer state
+        .manage(Mutex::new(AppConfig::from_env()))
+        .manage(SharedCallersCache::default())
+        .manage(SharedCalleesCache::default())
+        .manage(Mutex::new(Failures::default()))
+        .manage(SharedChangedFunctions::default())
+        .manage(SharedCoverage::default())
+        .manage(SharedRepoWatcher::default())
+        .manage(PendingCommands::default())
+        .manage(ShutdownFlag::default())
+        .manage(TraceStreams::default())
         .plugin(tauri_plugin_opener::init())
-        .invoke_handler(tauri::generate_handler![greet, get_flows, get_file_tree, get_tracer_data, get_function_signature])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .setup(|app| {
+            let config = app.state::<SharedConfig>();
+            // Packaged builds ship the tools as resources. Those are read-only,
+            // so the tracer logs to the app's log dir instead of next to them.
+            // Dev builds fall back to the source tree unless the bundle is
+            // complete; release builds have nothing else, so keep it regardless.
+            let bundled = app
+                .path()
+                .resource_dir()
+                .map(|dir| dir.join(BUNDLED_TOOLS_DIR))
+                .ok()
+                .filter(|dir| !cfg!(debug_assertions) || missing_tools(dir).is_empty());
+            let log_dir = bundled
+                .as_ref()
+                .and_then(|_| app.path().app_log_dir().ok())
+                .map(|dir| dir.join("tracer"));
+            let config_dir = app.path().app_config_dir()?;
+            let settings = Settings::load(config_dir.join("settings.json"));
+            let (tools_dir, python) = {
+                let mut config = config.lock().unwrap();
+                settings.apply(&mut config);
+                config.bundled_tools_dir = bundled;
+                config.tracer_log_dir = log_dir;
+                config.refresh_python();
+                (config.tools_dir(), config.checked_python())
+            };
+            if let Err(e) = python {
+                println!("[flowlens] warning: {}", e);
+            }
+            let missing = missing_tools(&tools_dir);
+            if !missing.is_empty() {
+                println!("[flowlens] warning: tools dir {} is missing {}", tools_dir.display(), missing.join(", "));
+            }
+
+            app.manage(Mutex::new(settings));
+            app.manage(Mutex::new(RecentFlows::load(config_dir.join("recent_flows.json"))));
+            app.manage(Mutex::new(BreakpointPresets::load(config_dir.join("breakpoint_presets.json"))));
+            Ok(())
+        })
+        .invoke_handler(move |invoke| {
+            // After `shutdown`, refuse everything but `resume` rather than restart what it stopped
+            if invoke.message.command() != "resume"
+                && invoke.message.webview_ref().state::<ShutdownFlag>().0.load(Ordering::SeqCst)
+            {
+                invoke.resolver.reject(FlowlensError::ShuttingDown);
+                return true;
+            }
+            handler(invoke)
+        })
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            if let RunEvent::Exit = event {
+                // Also after a panic poisoned a lock, so no tracer outlives the app
+                let stopped = stop_all_tracers(&app.state::<SharedTracer>());
+                if stopped > 0 {
+                    println!("[flowlens] app exiting, stopped {} tracer(s)", stopped);
+                }
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    /// A tracer running `script` under `sh` in place of get_tracer.py.
+    fn fake_tracer(script: &str) -> Tracer {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(script);
+        let req = TraceRequest {
+            entry_full_id: "/fake.py::run".to_string(),
+            ..Default::default()
+        };
+        Tracer::from_command(cmd, &req, &AppConfig::default(), None, false).unwrap()
+    }
+
+    #[test]
+    fn ensure_tracer_spawns_once_with_the_map_unlocked() {
+        let req = TraceRequest {
+            entry_full_id: "/fake.py::run".to_string(),
+            ..Default::default()
+        };
+        let config = AppConfig::default();
+        let tracers = SharedTracer::default();
+        let spawns = AtomicUsize::new(0);
+        let spawn = |_: &TraceRequest, _: &AppConfig| {
+            spawns.fetch_add(1, Ordering::SeqCst);
+            // Slow enough for every call to arrive while this one spawns
+            thread::sleep(Duration::from_millis(200));
+            // Other callers only take the map lock briefly, so it frees up quickly
+            let seen = (0..1000).find_map(|_| match tracers.map.try_lock() {
+                Ok(map) => Some(matches!(map.get(&req.key()), Some(TracerEntry::Spawning))),
+                Err(_) => {
+                    thread::sleep(Duration::from_millis(1));
+                    None
+                }
+            });
+            assert_eq!(seen, Some(true), "the map stayed locked during the spawn");
+            Ok(fake_tracer("read never"))
+        };
+
+        let spawned = thread::scope(|scope| {
+            let calls: Vec<_> = (0..8)
+                .map(|_| scope.spawn(|| ensure_tracer(&tracers, &req, &config, spawn, |_, reason| Ok(reason.is_some())).unwrap()))
+                .collect();
+            calls.into_iter().map(|call| call.join().unwrap()).filter(|spawned| *spawned).count()
+        });
+
+        assert_eq!(spawned, 1);
+        assert_eq!(spawns.load(Ordering::SeqCst), 1);
+        assert!(matches!(tracers.map.lock().unwrap().get(&req.key()), Some(TracerEntry::Ready(_))));
+    }
+
+    #[test]
+    fn read_step_event_drains_every_event_of_a_step() {
+        // Each step reports the line it stopped at and the next one, then the marker
+        let mut tracer = fake_tracer(
+            r#"step() {
+                echo "{\"event\":\"line\",\"phase\":\"line\",\"line\":$1}"
+                echo "{\"event\":\"line\",\"phase\":\"line\",\"line\":$(($1 + 1))}"
+                echo '{"event":"step_end"}'
+            }
+            step 1
+            while read stop; do step "$stop"; done"#,
+        );
+        let limits = PayloadLimits::default();
+
+        let first = tracer.read_step_event(&limits).unwrap();
+        assert_eq!(first["line"], 2);
+        assert_eq!(first["intermediate_events"][0]["line"], 1);
+
+        tracer.send_line("5".to_string()).unwrap();
+        let second = tracer.read_step_event(&limits).unwrap();
+        assert_eq!(second["line"], 6);
+        assert_eq!(second["intermediate_events"][0]["line"], 5);
+        assert_eq!(tracer.history.len(), 4);
+    }
+
+    #[test]
+    fn fnv1a64_matches_the_reference_vectors() {
+        assert_eq!(fnv1a64(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a64(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a64(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn send_line_times_out_when_the_tracer_never_reads_stdin() {
+        let tracer = fake_tracer("sleep 30");
+        // Far bigger than a pipe buffer, so the write blocks
+        let line = "x".repeat(1 << 20);
+        let started = Instant::now();
+        let result = tracer.send_line(line);
+        assert!(matches!(result, Err(FlowlensError::Timeout { .. })), "{:?}", result);
+        assert!(started.elapsed() < STDIN_WRITE_TIMEOUT + Duration::from_secs(2));
+    }
+
+    #[test]
+    fn kill_stops_a_tracer_blocked_forever_and_joins_its_readers() {
+        // Blocks in a read of stdin that never gets a line, like traced code in input()
+        let mut tracer = fake_tracer("read never");
+        let started = Instant::now();
+        tracer.kill();
+        assert!(tracer.reader.is_none() && tracer.diagnostics_reader.is_none());
+        // Detaching a still-blocked reader would take the whole join timeout
+        assert!(started.elapsed() < READER_JOIN_TIMEOUT, "took {:?}", started.elapsed());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn dropping_a_tracer_reaps_its_process() {
+        let tracer = fake_tracer("exec sleep 30");
+        let proc_entry = PathBuf::from(format!("/proc/{}", tracer.child.id()));
+        assert!(proc_entry.exists());
+        drop(tracer);
+        // A killed but unreaped child would linger as a zombie with its /proc entry
+        assert!(!proc_entry.exists());
+    }
+
+    #[test]
+    fn dropping_a_tracer_that_already_exited_does_not_panic() {
+        let mut tracer = fake_tracer("exit 0");
+        tracer.child.wait().unwrap();
+        drop(tracer);
+    }
+
+    #[test]
+    fn with_active_tracer_drops_a_tracer_that_timed_out() {
+        let mut tracer = fake_tracer("read never");
+        tracer.read_timeout = Duration::from_millis(100);
+        let tracers = SharedTracer::default();
+        tracers.map.lock().unwrap().insert(normalize_entry_id("/fake.py::run"), TracerEntry::Ready(TracerSlot::new(tracer)));
+
+        let result = with_active_tracer(&tracers, "/fake.py::run", |tracer| {
+            tracer.read_step_event(&PayloadLimits::default())
+        });
+        assert!(matches!(result, Err(FlowlensError::Timeout { .. })), "{:?}", result);
+        assert!(tracers.map.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn diagnostics_lines_reach_the_log_tail() {
+        // Events go to stdout, so the traceback lands on the diagnostics channel
+        let tracer = fake_tracer("read go; echo 'Traceback (most recent call last):' >&2; read never");
+        let (tx, rx) = mpsc::channel();
+        *tracer.log_tail.lock().unwrap() = Some(Arc::new(move |channel: &str, line: &str| {
+            let _ = tx.send((channel.to_string(), line.to_string()));
+        }));
+
+        tracer.send_line("go".to_string()).unwrap();
+        let (channel, line) = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(channel, "diagnostics");
+        assert_eq!(line, "Traceback (most recent call last):");
+        assert_eq!(tracer.diagnostics.lock().unwrap().back(), Some(&line));
+    }
+
+    #[test]
+    fn normalize_entry_id_adds_a_leading_slash() {
+        assert_eq!(normalize_entry_id("pkg/mod.py::run"), "/pkg/mod.py::run");
+        assert_eq!(normalize_entry_id("/pkg/mod.py::run"), "/pkg/mod.py::run");
+    }
+
+    #[test]
+    fn normalize_entry_id_accepts_backslashes() {
+        assert_eq!(normalize_entry_id("\\pkg\\mod.py::run"), "/pkg/mod.py::run");
+        assert_eq!(normalize_entry_id("pkg\\sub/mod.py::run"), "/pkg/sub/mod.py::run");
+    }
+
+    #[test]
+    fn normalize_entry_id_drops_dot_and_empty_segments() {
+        assert_eq!(normalize_entry_id("./pkg/mod.py::run"), "/pkg/mod.py::run");
+        assert_eq!(normalize_entry_id("//pkg//./mod.py::run"), "/pkg/mod.py::run");
+    }
+
+    #[test]
+    fn normalize_entry_id_trims_whitespace() {
+        assert_eq!(normalize_entry_id("  /pkg/mod.py :: run "), "/pkg/mod.py::run");
+        assert_eq!(normalize_entry_id(" pkg/mod.py::Order.total\n"), "/pkg/mod.py::Order.total");
+    }
+
+    #[test]
+    fn normalize_entry_id_keeps_ids_without_a_function() {
+        assert_eq!(normalize_entry_id("pkg/mod.py"), "/pkg/mod.py");
+    }
+
+    #[test]
+    fn tracer_key_separates_commits_of_one_flow() {
+        assert_eq!(tracer_key("pkg/mod.py::run", None), "/pkg/mod.py::run");
+        assert_eq!(tracer_key("pkg/mod.py::run", Some("")), "/pkg/mod.py::run");
+        assert_eq!(tracer_key("pkg/mod.py::run", Some("abc123")), "/pkg/mod.py::run@abc123");
+        // The key is itself an entry id that active_tracer can look up
+        assert_eq!(normalize_entry_id("/pkg/mod.py::run@abc123"), "/pkg/mod.py::run@abc123");
+    }
 }
@@ -0,0 +1,62 @@
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::process::output_logged;
+
+// ------------------------
+// Orphaned Tracer Processes
+// ------------------------
+
+#[derive(Serialize, Debug)]
+pub struct TracerProcess {
+    pub pid: u32,
+    /// Full argv, space-joined
+    pub cmdline: String,
+}
+
+/// Running processes whose argv includes `script` (matched after resolving
+/// relative paths against each process's cwd), other than those in `tracked`.
+/// Found by scanning `/proc`, so this is always empty where that doesn't exist.
+pub fn find_untracked(script: &Path, tracked: &[u32]) -> Vec<TracerProcess> {
+    let Ok(script) = script.canonicalize() else {
+        return Vec::new();
+    };
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+
+    entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+        .filter(|pid| *pid != std::process::id() && !tracked.contains(pid))
+        .filter_map(|pid| {
+            let raw = std::fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+            let argv: Vec<String> = raw
+                .split(|b| *b == 0)
+                .filter(|arg| !arg.is_empty())
+                .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                .collect();
+            let cwd = std::fs::read_link(format!("/proc/{}/cwd", pid)).unwrap_or_default();
+            argv.iter()
+                .any(|arg| runs_script(&cwd, arg, &script))
+                .then(|| TracerProcess {
+                    pid,
+                    cmdline: argv.join(" "),
+                })
+        })
+        .collect()
+}
+
+fn runs_script(cwd: &Path, arg: &str, script: &Path) -> bool {
+    if Path::new(arg).file_name() != script.file_name() {
+        return false;
+    }
+    let path: PathBuf = cwd.join(arg);
+    path.canonicalize().is_ok_and(|path| path == script)
+}
+
+/// Send SIGKILL to `pid`. Returns whether `kill` reported success.
+pub fn kill(pid: u32) -> bool {
+    output_logged(Command::new("kill").arg("-9").arg(pid.to_string())).is_ok_and(|o| o.status.success())
+}
@@ -0,0 +1,71 @@
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+// ------------------------
+// Repo-Relative Paths
+// ------------------------
+
+/// Object keys whose string values are file paths.
+const PATH_KEYS: &[&str] = &["filename", "file", "path"];
+
+/// Rewrites absolute paths under a repo root to repo-relative ones, so events
+/// and trees don't carry the machine-specific prefix.
+pub struct RepoPaths {
+    roots: Vec<PathBuf>,
+}
+
+impl RepoPaths {
+    /// Paths are matched against `repo` both as given and canonicalized, since
+    /// Python reports whichever form it was started with.
+    pub fn new(repo: &Path) -> Self {
+        let mut roots = vec![repo.to_path_buf()];
+        if let Ok(canonical) = repo.canonicalize() {
+            if canonical != repo {
+                roots.push(canonical);
+            }
+        }
+        Self { roots }
+    }
+
+    /// The repo root as given to `new`.
+    pub fn root(&self) -> &Path {
+        &self.roots[0]
+    }
+
+    /// `path` relative to the repo, with `/` separators, or `None` when it is
+    /// not an absolute path inside the repo.
+    pub fn relative(&self, path: &str) -> Option<String> {
+        let path = Path::new(path);
+        if !path.is_absolute() {
+            return None;
+        }
+        let rel = self.roots.iter().find_map(|root| path.strip_prefix(root).ok())?;
+        Some(rel.to_string_lossy().replace('\\', "/"))
+    }
+
+    /// Rewrite every path field in `value` (see `PATH_KEYS`) that points into
+    /// the repo. The original is kept alongside as `absolute`.
+    pub fn relativize(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                let mut absolute = None;
+                for key in PATH_KEYS {
+                    if let Some(Value::String(path)) = map.get_mut(*key) {
+                        if let Some(rel) = self.relative(path) {
+                            absolute.get_or_insert_with(|| path.clone());
+                            *path = rel;
+                        }
+                    }
+                }
+                for item in map.values_mut() {
+                    self.relativize(item);
+                }
+                if let Some(absolute) = absolute {
+                    map.entry("absolute").or_insert(Value::String(absolute));
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.relativize(item)),
+            _ => {}
+        }
+    }
+}
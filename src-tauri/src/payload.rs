@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// ------------------------
+// Event Payload Budget
+// ------------------------
+
+/// Strings are never cut below this many characters while fitting the total budget.
+const MIN_FIELD_LEN: usize = 64;
+
+/// Size limits applied to every trace event before it crosses the IPC bridge.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct PayloadLimits {
+    /// Longest string (in characters) kept for a single value
+    pub max_field_len: usize,
+    /// Largest serialized event (in bytes) returned to the UI
+    pub max_payload_bytes: usize,
+}
+
+impl Default for PayloadLimits {
+    fn default() -> Self {
+        Self {
+            max_field_len: 10_000,
+            max_payload_bytes: 1_000_000,
+        }
+    }
+}
+
+/// Truncate oversized strings in `event` until it fits `limits`. Truncated
+/// values keep a `...` suffix and their JSON paths (e.g. `locals.rows`) are
+/// listed under `truncated_fields`; `payload_truncated` says whether anything
+/// was cut. Lists and dicts are left whole, so an event with very many small
+/// values can still exceed the byte budget.
+pub fn enforce_limits(event: &mut Value, limits: &PayloadLimits) {
+    let mut field_len = limits.max_field_len;
+    let mut truncated = Vec::new();
+    truncate_strings(event, field_len, "", &mut truncated);
+
+    while field_len > MIN_FIELD_LEN && serialized_len(event) > limits.max_payload_bytes {
+        field_len = (field_len / 2).max(MIN_FIELD_LEN);
+        truncate_strings(event, field_len, "", &mut truncated);
+    }
+
+    truncated.sort();
+    truncated.dedup();
+    if let Value::Object(map) = event {
+        map.insert("payload_truncated".to_string(), Value::Bool(!truncated.is_empty()));
+        if !truncated.is_empty() {
+            map.insert("truncated_fields".to_string(), truncated.into());
+        }
+    }
+}
+
+fn serialized_len(value: &Value) -> usize {
+    serde_json::to_vec(value).map(|v| v.len()).unwrap_or(0)
+}
+
+fn truncate_strings(value: &mut Value, max_len: usize, path: &str, truncated: &mut Vec<String>) {
+    let child_path = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+
+    match value {
+        // +3 leaves room for the marker so re-truncating is a no-op
+        Value::String(text) if text.chars().count() > max_len + 3 => {
+            let cut = text.char_indices().nth(max_len).map(|(i, _)| i).unwrap_or(text.len());
+            text.truncate(cut);
+            text.push_str("...");
+            truncated.push(path.to_string());
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                truncate_strings(item, max_len, &child_path(&i.to_string()), truncated);
+            }
+        }
+        Value::Object(map) => {
+            for (key, item) in map.iter_mut() {
+                truncate_strings(item, max_len, &child_path(key), truncated);
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+// ------------------------
+// Outstanding Step Commands
+// ------------------------
+
+/// Stepping commands issued per flow and not answered yet. Commands on a flow
+/// run one at a time under its tracer's lock, so this counts the one running
+/// plus any still waiting for the lock. Kept outside the tracer so it can be read while a step
+/// holds that lock.
+#[derive(Default)]
+pub struct PendingCommands {
+    counts: Mutex<HashMap<String, usize>>,
+}
+
+impl PendingCommands {
+    /// Count a command for `flow` until the returned guard is dropped.
+    pub fn start(&self, flow: &str) -> PendingGuard<'_> {
+        *self.counts.lock().unwrap().entry(flow.to_string()).or_default() += 1;
+        PendingGuard {
+            pending: self,
+            flow: flow.to_string(),
+        }
+    }
+
+    pub fn depth(&self, flow: &str) -> usize {
+        self.counts.lock().unwrap().get(flow).copied().unwrap_or(0)
+    }
+}
+
+pub struct PendingGuard<'a> {
+    pending: &'a PendingCommands,
+    flow: String,
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        let mut counts = self.pending.counts.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(count) = counts.get_mut(&self.flow) {
+            *count -= 1;
+            if *count == 0 {
+                counts.remove(&self.flow);
+            }
+        }
+    }
+}
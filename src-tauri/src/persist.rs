@@ -0,0 +1,31 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::Path;
+
+use crate::error::FlowlensError;
+
+// ------------------------
+// JSON Persistence
+// ------------------------
+
+/// Read `path` as JSON, falling back to `T::default()` when it is missing or
+/// unreadable, so a damaged file never keeps the app from starting.
+pub fn load_json<T: DeserializeOwned + Default>(path: &Path) -> T {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+/// Write `value` to `path` as pretty JSON, creating the directory first.
+/// Without a path (state that was never loaded from disk) nothing is written.
+pub fn save_json<T: Serialize>(path: Option<&Path>, value: &T) -> Result<(), FlowlensError> {
+    let Some(path) = path else {
+        return Ok(());
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| FlowlensError::io(dir, e))?;
+    }
+    let text = serde_json::to_string_pretty(value).map_err(|e| FlowlensError::io(path, e))?;
+    std::fs::write(path, text).map_err(|e| FlowlensError::io(path, e))
+}
@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::error::FlowlensError;
+use crate::persist::{load_json, save_json};
+use crate::Breakpoint;
+
+// ------------------------
+// Breakpoint Presets
+// ------------------------
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BreakpointPreset {
+    pub name: String,
+    pub breakpoints: Vec<Breakpoint>,
+}
+
+/// Named breakpoint sets, persisted as JSON so a layout can be reused and shared.
+#[derive(Default)]
+pub struct BreakpointPresets {
+    path: Option<PathBuf>,
+    presets: BTreeMap<String, Vec<Breakpoint>>,
+}
+
+impl BreakpointPresets {
+    /// The presets saved at `path`; `save_preset` writes back there.
+    pub fn load(path: PathBuf) -> Self {
+        Self {
+            presets: load_json(&path),
+            path: Some(path),
+        }
+    }
+
+    /// Store `breakpoints` under `name`, replacing any preset of that name.
+    pub fn save_preset(&mut self, name: &str, breakpoints: Vec<Breakpoint>) -> Result<(), FlowlensError> {
+        self.presets.insert(name.to_string(), breakpoints);
+        save_json(self.path.as_deref(), &self.presets)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&[Breakpoint]> {
+        self.presets.get(name).map(Vec::as_slice)
+    }
+
+    /// Every preset, sorted by name.
+    pub fn list(&self) -> Vec<BreakpointPreset> {
+        self.presets
+            .iter()
+            .map(|(name, breakpoints)| BreakpointPreset {
+                name: name.clone(),
+                breakpoints: breakpoints.clone(),
+            })
+            .collect()
+    }
+}
+
+pub type SharedBreakpointPresets = Mutex<BreakpointPresets>;
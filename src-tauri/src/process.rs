@@ -0,0 +1,174 @@
+use std::collections::BTreeSet;
+use std::io;
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::Mutex;
+
+use crate::error::FlowlensError;
+
+// ------------------------
+// Subprocess Logging
+// ------------------------
+
+/// Environment variables that change how the Python tools behave, logged with
+/// every spawn alongside any the command sets itself.
+const LOGGED_ENV: &[&str] = &["PYTHON_BIN", "PYTHONPATH", "VIRTUAL_ENV", "FLOWLENS_ALLOWED_ROOTS"];
+
+/// Spawn `cmd`, logging exactly what runs (program, argv, cwd, env) and the pid,
+/// so "what command actually ran" can be answered from the logs.
+pub fn spawn_logged(cmd: &mut Command) -> io::Result<Child> {
+    let argv = argv(cmd);
+    let cwd = match cmd.get_current_dir() {
+        Some(dir) => dir.display().to_string(),
+        None => std::env::current_dir()
+            .map(|d| d.display().to_string())
+            .unwrap_or_else(|_| "?".to_string()),
+    };
+    let mut env: Vec<String> = LOGGED_ENV
+        .iter()
+        .filter_map(|key| std::env::var(key).ok().map(|value| format!("{}={}", key, value)))
+        .collect();
+    env.extend(cmd.get_envs().map(|(key, value)| {
+        format!(
+            "{}={}",
+            key.to_string_lossy(),
+            value.map(|v| v.to_string_lossy().into_owned()).unwrap_or_default()
+        )
+    }));
+
+    println!("[flowlens] [debug] spawn argv={:?} cwd={} env={:?}", argv, cwd, env);
+    match cmd.spawn() {
+        Ok(child) => {
+            println!("[flowlens] [debug] spawned {} pid={}", argv[0], child.id());
+            Ok(child)
+        }
+        Err(e) => {
+            println!("[flowlens] [debug] spawn of {} failed: {}", argv[0], e);
+            Err(e)
+        }
+    }
+}
+
+/// Program and arguments of `cmd`, as strings.
+pub fn argv(cmd: &Command) -> Vec<String> {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|a| a.to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Pids of `output_logged` children that haven't been waited for yet.
+static RUNNING: Mutex<BTreeSet<u32>> = Mutex::new(BTreeSet::new());
+
+/// Like `Command::output`, but spawned through `spawn_logged` and logging the exit status.
+pub fn output_logged(cmd: &mut Command) -> io::Result<Output> {
+    let child = spawn_logged(cmd.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()))?;
+    let pid = child.id();
+    RUNNING.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).insert(pid);
+    let output = child.wait_with_output();
+    RUNNING.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).remove(&pid);
+    let output = output?;
+    log_exit(pid, output.status);
+    Ok(output)
+}
+
+/// `output_logged` for a command that must succeed, such as git: its output
+/// if it exited with status 0, otherwise `ScriptFailed` under `name` with its
+/// stderr, also when it couldn't be started at all.
+pub fn checked_output(name: &str, cmd: &mut Command) -> Result<Output, FlowlensError> {
+    let output = output_logged(cmd).map_err(|e| FlowlensError::ScriptFailed {
+        script: name.to_string(),
+        code: None,
+        stderr: format!("could not start: {}", e),
+    })?;
+    if !output.status.success() {
+        return Err(FlowlensError::ScriptFailed {
+            script: name.to_string(),
+            code: output.status.code(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        });
+    }
+    Ok(output)
+}
+
+/// Children of `output_logged` still running, e.g. a slow tool or git call.
+pub fn running_pids() -> Vec<u32> {
+    RUNNING.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).iter().copied().collect()
+}
+
+/// Pids of tracer processes not reaped yet, so `kill_tracers` can reach them
+/// without the locks of the commands using them. A pid is only forgotten
+/// while this is locked and before it is reaped, so a kill never hits a reused pid.
+static TRACERS: Mutex<BTreeSet<u32>> = Mutex::new(BTreeSet::new());
+
+/// Remember a freshly spawned tracer process for `kill_tracers`.
+pub fn track_tracer(pid: u32) {
+    TRACERS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).insert(pid);
+}
+
+/// `Child::try_wait` for a tracer: once it has exited, it is reaped and forgotten.
+pub fn try_reap_tracer(child: &mut Child) -> io::Result<Option<ExitStatus>> {
+    let mut tracers = TRACERS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let status = child.try_wait();
+    if let Ok(Some(_)) = status {
+        tracers.remove(&child.id());
+    }
+    status
+}
+
+/// Kill a tracer process and reap it. Harmless if it already exited.
+pub fn kill_and_reap_tracer(child: &mut Child) -> io::Result<ExitStatus> {
+    let _ = child.kill(); // Ignore errors if already dead
+    // Dead but unreaped, its pid can't be reused yet
+    TRACERS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).remove(&child.id());
+    child.wait()
+}
+
+/// SIGKILL every tracer process not reaped yet; their `Tracer`s reap them.
+/// Takes no lock but this module's own. Returns how many were killed.
+pub fn kill_tracers() -> usize {
+    let tracers = TRACERS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    tracers.iter().filter(|pid| crate::orphans::kill(**pid)).count()
+}
+
+pub fn log_exit(pid: u32, status: ExitStatus) {
+    println!("[flowlens] [debug] pid={} exited with {}", pid, status);
+}
+
+/// The signal that terminated the process, if it didn't exit on its own.
+/// Always `None` off Unix, where there are no signals.
+#[cfg(unix)]
+pub fn exit_signal(status: ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+pub fn exit_signal(_status: ExitStatus) -> Option<i32> {
+    None
+}
+
+/// How the process ended, for messages: `exited with code 1`, or
+/// `was killed by signal 9 (SIGKILL)`.
+pub fn describe_exit(status: ExitStatus) -> String {
+    match (status.code(), exit_signal(status)) {
+        (Some(code), _) => format!("exited with code {}", code),
+        (None, Some(signal)) => match signal_name(signal) {
+            Some(name) => format!("was killed by signal {} ({})", signal, name),
+            None => format!("was killed by signal {}", signal),
+        },
+        (None, None) => format!("exited with {}", status),
+    }
+}
+
+/// Names of the signals a crashed or killed tracer usually dies of.
+fn signal_name(signal: i32) -> Option<&'static str> {
+    match signal {
+        1 => Some("SIGHUP"),
+        2 => Some("SIGINT"),
+        6 => Some("SIGABRT"),
+        9 => Some("SIGKILL"),
+        11 => Some("SIGSEGV"),
+        15 => Some("SIGTERM"),
+        _ => None,
+    }
+}
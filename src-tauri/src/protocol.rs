@@ -0,0 +1,68 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::FlowlensError;
+
+// ------------------------
+// Tracer Event Protocol
+// ------------------------
+
+/// Python trace event a step stopped on: about to run a line, entering or
+/// leaving a function, or at the line that just raised.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+    Line,
+    Call,
+    Return,
+    Exception,
+}
+
+impl Phase {
+    /// The `phase` of `event`, or `None` for events that aren't steps
+    /// (errors, `expand` results). An unknown value is an error.
+    pub fn of(event: &Value) -> Result<Option<Self>, FlowlensError> {
+        match event.get("phase") {
+            None | Some(Value::Null) => Ok(None),
+            Some(phase) => serde_json::from_value(phase.clone())
+                .map(Some)
+                .map_err(|_| FlowlensError::InvalidJson {
+                    message: format!("tracer sent an unknown phase: {}", phase),
+                }),
+        }
+    }
+}
+
+/// The instance or class a method frame runs on, reported as an event's
+/// `receiver`. Plain functions have none.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Receiver {
+    pub kind: ReceiverKind,
+    /// Class name: of the instance for `self`, the class itself for `cls`
+    #[serde(rename = "type")]
+    pub type_name: String,
+    pub repr: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReceiverKind {
+    #[serde(rename = "self")]
+    SelfValue,
+    Cls,
+}
+
+impl Receiver {
+    /// The `receiver` of `event`, or `None` for plain functions and events
+    /// without a frame. A malformed value is an error.
+    pub fn of(event: &Value) -> Result<Option<Self>, FlowlensError> {
+        match event.get("receiver") {
+            None | Some(Value::Null) => Ok(None),
+            Some(receiver) => serde_json::from_value(receiver.clone())
+                .map(Some)
+                .map_err(|e| FlowlensError::InvalidJson {
+                    message: format!("tracer sent a malformed receiver {}: {}", receiver, e),
+                }),
+        }
+    }
+}
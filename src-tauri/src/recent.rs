@@ -0,0 +1,105 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::persist::{load_json, save_json};
+use crate::TraceRequest;
+
+// ------------------------
+// Recently Traced Flows
+// ------------------------
+
+/// How many flows are remembered before the oldest is dropped.
+const MAX_RECENT_FLOWS: usize = 20;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RecentFlow {
+    pub entry_full_id: String,
+    /// The request that last spawned a tracer for this flow, so the UI can replay it
+    pub request: TraceRequest,
+    /// Seconds since the Unix epoch
+    pub last_used: u64,
+}
+
+/// Most-recently-used list of traced flows, newest first, persisted as JSON.
+#[derive(Default)]
+pub struct RecentFlows {
+    path: Option<PathBuf>,
+    entries: Vec<RecentFlow>,
+}
+
+impl RecentFlows {
+    /// The list saved at `path`, kept up to date there by `record`.
+    pub fn load(path: PathBuf) -> Self {
+        Self {
+            entries: load_json(&path),
+            path: Some(path),
+        }
+    }
+
+    /// Put `req` first, replacing the entry for the same flow and commit
+    /// however its id was spelled.
+    pub fn record(&mut self, req: &TraceRequest) {
+        let last_used = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        let key = req.key();
+        self.entries.retain(|f| f.request.key() != key);
+        self.entries.insert(
+            0,
+            RecentFlow {
+                entry_full_id: req.entry_full_id.clone(),
+                // The id belongs to the action that ran it, not to replays
+                request: TraceRequest { request_id: None, ..req.clone() },
+                last_used,
+            },
+        );
+        self.entries.truncate(MAX_RECENT_FLOWS);
+
+        if let Err(e) = save_json(self.path.as_deref(), &self.entries) {
+            println!("[flowlens] failed to save recent flows: {}", e);
+        }
+    }
+
+    pub fn newest(&self, limit: usize) -> Vec<RecentFlow> {
+        self.entries.iter().take(limit).cloned().collect()
+    }
+}
+
+pub type SharedRecentFlows = Mutex<RecentFlows>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(entry_full_id: &str, at_commit: Option<&str>) -> TraceRequest {
+        TraceRequest {
+            entry_full_id: entry_full_id.to_string(),
+            at_commit: at_commit.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn record_replaces_the_same_flow_spelled_differently() {
+        let mut recent = RecentFlows::default();
+        recent.record(&request("/orders.py::total", None));
+        recent.record(&request("/payments.py::charge", None));
+        recent.record(&request("orders.py::total", None));
+
+        let ids: Vec<String> = recent.newest(10).into_iter().map(|f| f.entry_full_id).collect();
+        assert_eq!(ids, ["orders.py::total", "/payments.py::charge"]);
+    }
+
+    #[test]
+    fn record_keeps_a_flow_at_another_commit_apart() {
+        let mut recent = RecentFlows::default();
+        recent.record(&request("/orders.py::total", None));
+        recent.record(&request("/orders.py::total", Some("abc123")));
+
+        assert_eq!(recent.newest(10).len(), 2);
+    }
+}
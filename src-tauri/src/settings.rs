@@ -0,0 +1,84 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::config::AppConfig;
+use crate::error::FlowlensError;
+use crate::persist::{load_json, save_json};
+
+// ------------------------
+// Saved Settings
+// ------------------------
+
+/// Values set at runtime that should survive a restart. Only what was set is stored.
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct SavedSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_timeout_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tracers: Option<usize>,
+}
+
+/// The saved settings, persisted as JSON in the app config dir.
+#[derive(Default)]
+pub struct Settings {
+    path: Option<PathBuf>,
+    saved: SavedSettings,
+}
+
+impl Settings {
+    /// The settings saved at `path`; `update` writes back there.
+    pub fn load(path: PathBuf) -> Self {
+        Self {
+            saved: load_json(&path),
+            path: Some(path),
+        }
+    }
+
+    /// Fill in what was saved on top of `AppConfig::from_env`, only where no
+    /// env var already set a value: env vars take precedence over this file.
+    pub fn apply(&self, config: &mut AppConfig) {
+        config.read_timeout_secs = config.read_timeout_secs.or(self.saved.read_timeout_secs);
+        config.max_tracers = config.max_tracers.or(self.saved.max_tracers);
+    }
+
+    /// Change the saved settings with `f` and write them out.
+    pub fn update(&mut self, f: impl FnOnce(&mut SavedSettings)) -> Result<(), FlowlensError> {
+        f(&mut self.saved);
+        save_json(self.path.as_deref(), &self.saved)
+    }
+}
+
+pub type SharedSettings = Mutex<Settings>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn saved(read_timeout_secs: Option<u64>, max_tracers: Option<usize>) -> Settings {
+        Settings {
+            path: None,
+            saved: SavedSettings { read_timeout_secs, max_tracers },
+        }
+    }
+
+    #[test]
+    fn apply_fills_in_values_no_env_var_set() {
+        let mut config = AppConfig::default();
+        saved(Some(60), Some(3)).apply(&mut config);
+        assert_eq!(config.read_timeout_secs, Some(60));
+        assert_eq!(config.max_tracers, Some(3));
+    }
+
+    #[test]
+    fn apply_keeps_the_env_timeout_over_the_saved_one() {
+        // As left by `from_env` with FLOWLENS_TIMEOUT=5
+        let mut config = AppConfig {
+            read_timeout_secs: Some(5),
+            ..Default::default()
+        };
+        saved(Some(60), None).apply(&mut config);
+        assert_eq!(config.read_timeout_secs, Some(5));
+        assert_eq!(config.max_tracers, None);
+    }
+}
@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+// ------------------------
+// Streamed Traces
+// ------------------------
+
+/// Traces stepping on a background thread, by flow. Each one checks its stop
+/// flag between steps.
+#[derive(Default)]
+pub struct TraceStreams {
+    running: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl TraceStreams {
+    /// Register a stream for `flow` and return its stop flag, or `None` if
+    /// one is already running for it.
+    pub fn start(&self, flow: &str) -> Option<Arc<AtomicBool>> {
+        let mut running = self.running.lock().unwrap();
+        if running.contains_key(flow) {
+            return None;
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        running.insert(flow.to_string(), stop.clone());
+        Some(stop)
+    }
+
+    /// Ask the stream for `flow` to stop after its current step. Returns
+    /// whether one was running.
+    pub fn stop(&self, flow: &str) -> bool {
+        match self.running.lock().unwrap().get(flow) {
+            Some(stop) => {
+                stop.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Ask every stream to stop. Returns how many were running.
+    pub fn stop_all(&self) -> usize {
+        let running = self.running.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        for stop in running.values() {
+            stop.store(true, Ordering::SeqCst);
+        }
+        running.len()
+    }
+
+    /// Called by the stream for `flow` as it ends.
+    pub fn finished(&self, flow: &str) {
+        self.running.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).remove(flow);
+    }
+}
@@ -0,0 +1,88 @@
+use serde::Serialize;
+
+// ------------------------
+// Python Tool Contract
+// ------------------------
+
+pub const CHANGED_FUNCTIONS: &str = "get_changed_functions.py";
+pub const FILE_TREE: &str = "get_file_tree.py";
+pub const TRACER: &str = "get_tracer.py";
+
+/// One command-line argument the app passes a tool.
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct ToolArg {
+    pub flag: &'static str,
+    /// Placeholder for the value, `None` for switches
+    pub value: Option<&'static str>,
+    pub note: &'static str,
+}
+
+/// A script the app runs and every argument it may pass it.
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct ToolSpec {
+    pub script: &'static str,
+    pub purpose: &'static str,
+    pub args: &'static [ToolArg],
+}
+
+const fn arg(flag: &'static str, value: &'static str, note: &'static str) -> ToolArg {
+    ToolArg { flag, value: Some(value), note }
+}
+
+const fn switch(flag: &'static str, note: &'static str) -> ToolArg {
+    ToolArg { flag, value: None, note }
+}
+
+/// Every script the app runs, all expected directly inside the tools dir.
+/// Keep in step with the spawns in `lib.rs`.
+pub const TOOLS: &[ToolSpec] = &[
+    ToolSpec {
+        script: CHANGED_FUNCTIONS,
+        purpose: "changed functions and their call graph, from git diff",
+        args: &[
+            arg("--repo", "<repo>", "always"),
+            switch("--names-only", "list_changed_functions"),
+            arg("--base_ref", "<ref>", "list_changed_functions, when a base ref is given"),
+        ],
+    },
+    ToolSpec {
+        script: FILE_TREE,
+        purpose: "directory tree of the repo",
+        args: &[
+            arg("--root", "<repo>", "always"),
+            switch("--with-metadata", "get_file_tree with with_metadata"),
+        ],
+    },
+    ToolSpec {
+        script: TRACER,
+        purpose: "tracing, plus one-shot static and import queries",
+        args: &[
+            arg("--repo_root", "<repo>", "always"),
+            arg("--entry_full_id", "<id>", "always"),
+            arg("--sys-path", "<dir>", "once per set_sys_path entry"),
+            arg("--args_json", "<json>", "tracing"),
+            arg("--stop_line", "<line>", "tracing"),
+            arg("--entry_kind", "<kind>", "tracing"),
+            arg("--events-on", "stdout|stderr", "tracing"),
+            arg("--backend", "settrace", "tracing, per tracer_backend"),
+            arg("--step_scope", "project|all", "tracing"),
+            arg("--with-context", "<lines>", "tracing, per set_context_lines"),
+            arg("--setup_expr", "<expr>", "tracing, when set"),
+            switch("--stop_at_entry", "tracing, per default_stop"),
+            arg("--seed", "<n>", "tracing, when random_seed is set"),
+            arg("--breakpoints", "<file:line,...>", "tracing, when breakpoints are set"),
+            switch("--prewarm", "prewarm_tracer"),
+            switch("--run_to_end", "run_to_end, profile_run"),
+            switch("--profile", "profile_run"),
+            switch("--get_signature", "signature queries"),
+            switch("--with_decorators", "get_function_signature"),
+            switch("--with_complexity", "function_metrics"),
+            switch("--get_callees", "get_callees"),
+            switch("--get_callers", "get_callers"),
+            switch("--cfg", "next_step_targets"),
+            switch("--analyze", "estimate_trace"),
+            switch("--source", "annotated_source"),
+            switch("--check_import", "check_importable"),
+        ],
+    },
+];
@@ -0,0 +1,134 @@
+use notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::error::FlowlensError;
+
+// ------------------------
+// Repo Change Watcher
+// ------------------------
+
+/// How long a path must go without events before its change is reported, so
+/// a burst of edits (a save touching several files, a checkout) comes as one call.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Directory names never reported: tooling state, not source.
+const SKIPPED_DIRS: &[&str] = &["__pycache__", "venv", "node_modules"];
+
+/// Watches a repo for added, modified and removed files through the OS's file
+/// events, calling back on a thread of its own. Stops when dropped, or with
+/// `stop` to also wait for that thread.
+pub struct RepoWatcher {
+    root: PathBuf,
+    /// Dropping this ends the watch, and with it the callback thread
+    debouncer: Debouncer<RecommendedWatcher>,
+    thread: JoinHandle<()>,
+}
+
+impl RepoWatcher {
+    /// Start watching `root`. After a burst of changes settles, `on_change`
+    /// gets every path that changed in it, repo-relative and sorted. Fails when
+    /// the OS won't watch `root`, e.g. when out of inotify watches.
+    pub fn start(root: &Path, on_change: impl Fn(Vec<String>) + Send + 'static) -> Result<Self, FlowlensError> {
+        let (events, received) = mpsc::channel::<DebounceEventResult>();
+        let mut debouncer = new_debouncer(DEBOUNCE, events).map_err(|e| FlowlensError::io(root, e))?;
+        debouncer
+            .watcher()
+            .watch(root, RecursiveMode::Recursive)
+            .map_err(|e| FlowlensError::io(root, e))?;
+
+        let repo = root.to_path_buf();
+        let thread = thread::spawn(move || {
+            // Ends once the debouncer is dropped along with its sender
+            for result in received {
+                let events = match result {
+                    Ok(events) => events,
+                    Err(e) => {
+                        println!("[flowlens] repo watch error: {}", e);
+                        continue;
+                    }
+                };
+                let mut paths: Vec<String> = events.iter().filter_map(|event| relative(&repo, &event.path)).collect();
+                paths.sort();
+                paths.dedup();
+                if !paths.is_empty() {
+                    on_change(paths);
+                }
+            }
+        });
+        Ok(Self {
+            root: root.to_path_buf(),
+            debouncer,
+            thread,
+        })
+    }
+
+    /// Stop watching and wait for the thread to exit, including a change
+    /// callback it is in the middle of.
+    pub fn stop(self) {
+        drop(self.debouncer);
+        let _ = self.thread.join();
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+}
+
+/// `path` relative to `repo` with `/` separators, or `None` for the repo
+/// itself, paths outside it, and anything under a hidden entry or `SKIPPED_DIRS`.
+fn relative(repo: &Path, path: &Path) -> Option<String> {
+    let rel = path.strip_prefix(repo).ok()?;
+    let mut parts = Vec::new();
+    for component in rel.components() {
+        let Component::Normal(name) = component else {
+            return None;
+        };
+        let name = name.to_string_lossy();
+        if name.starts_with('.') || SKIPPED_DIRS.contains(&name.as_ref()) {
+            return None;
+        }
+        parts.push(name.into_owned());
+    }
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+pub type SharedRepoWatcher = Mutex<Option<RepoWatcher>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_skips_hidden_and_tooling_paths() {
+        let repo = Path::new("/repo");
+        assert_eq!(relative(repo, Path::new("/repo/app/orders.py")).as_deref(), Some("app/orders.py"));
+        assert_eq!(relative(repo, Path::new("/repo/.git/index")), None);
+        assert_eq!(relative(repo, Path::new("/repo/app/__pycache__/orders.pyc")), None);
+        assert_eq!(relative(repo, Path::new("/repo")), None);
+        assert_eq!(relative(repo, Path::new("/elsewhere/orders.py")), None);
+    }
+
+    #[test]
+    fn a_burst_of_writes_is_reported_once_it_settles() {
+        let repo = std::env::temp_dir().join(format!("flowlens-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&repo).unwrap();
+        let (tx, rx) = mpsc::channel();
+        let watcher = RepoWatcher::start(&repo, move |paths| {
+            let _ = tx.send(paths);
+        })
+        .unwrap();
+
+        std::fs::write(repo.join("orders.py"), "def total():\n    return 1\n").unwrap();
+        std::fs::write(repo.join("payments.py"), "def charge():\n    pass\n").unwrap();
+        let changed = rx.recv_timeout(Duration::from_secs(10));
+        watcher.stop();
+        let _ = std::fs::remove_dir_all(&repo);
+
+        assert_eq!(changed.unwrap(), ["orders.py", "payments.py"]);
+    }
+}
@@ -0,0 +1,68 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::FlowlensError;
+use crate::process::{checked_output, output_logged};
+
+// ------------------------
+// Temporary Git Worktrees
+// ------------------------
+
+/// A detached checkout of one commit in a temp directory, removed again on
+/// drop, so older code can be traced without touching the working tree.
+pub struct Worktree {
+    repo: PathBuf,
+    path: PathBuf,
+}
+
+impl Worktree {
+    pub fn create(repo: &Path, commit: &str) -> Result<Self, FlowlensError> {
+        if commit.is_empty() || commit.starts_with('-') {
+            return Err(FlowlensError::InvalidRequest {
+                message: format!("invalid commit: {}", commit),
+            });
+        }
+
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        let path = std::env::temp_dir().join(format!("flowlens-worktree-{}-{}", std::process::id(), nanos));
+
+        checked_output(
+            "git worktree add",
+            Command::new("git")
+                .arg("-C")
+                .arg(repo)
+                .args(["worktree", "add", "--detach"])
+                .arg(&path)
+                .arg(commit),
+        )?;
+
+        println!("[flowlens] created worktree for {} at {}", commit, path.display());
+        Ok(Self {
+            repo: repo.to_path_buf(),
+            path,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Worktree {
+    fn drop(&mut self) {
+        let removed = output_logged(
+            Command::new("git")
+                .arg("-C")
+                .arg(&self.repo)
+                .args(["worktree", "remove", "--force"])
+                .arg(&self.path),
+        );
+        if !removed.is_ok_and(|o| o.status.success()) {
+            println!("[flowlens] failed to remove worktree {}", self.path.display());
+        }
+    }
+}
@@ -97,14 +97,10 @@ export default function App() {
     // extract file path before ::
     let path = fullId.split("::")[0]; // "/backend/services/analytics_processor.py"
 
-    // Remove leading slash if your FileNode paths are relative to repo root
+    // FileNode paths are relative to the repo root
     if (path.startsWith("/")) path = path.slice(1);
 
-    // Optional: prepend repo root if needed
-    const repoRoot = "/home/bimal/Documents/ucsd/research/code/trap/";
-    const fullPath = repoRoot + path;
-
-    fileExplorerRef.current?.highlightFile(fullPath);
+    fileExplorerRef.current?.highlightFile(path);
   }, []);
   return (
     <div
@@ -12,6 +12,8 @@ interface TraceEvent {
   line?: number;
   locals?: Record<string, any>;
   globals?: Record<string, any>;
+  // Source lines around `line`, when the tracer was started with context
+  context?: { start: number; lines: string[] };
   value?: any;
   result?: any;
   error?: string;
@@ -368,7 +370,8 @@ const FlowPanel: React.FC<FlowPanelProps> = ({
           console.error("Error calling tracer:", err);
           event = {
             event: "error",
-            error: err?.toString() || "Failed to communicate with Python tracer",
+            // Rust errors arrive as strings or as { kind, ... } objects
+            error: (typeof err === "string" ? err : err?.message || (err?.kind && JSON.stringify(err))) || "Failed to communicate with Python tracer",
             traceback: err?.message || "The tracer process may have crashed or timed out.",
             line: line,
             filename: filename,
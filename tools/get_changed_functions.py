@@ -18,7 +18,7 @@ CALL_RE_WITH_PATH = re.compile(r"(?:/\S+::)?([A-Za-z_]\w*)\s*\(")
 DEF_LINE_RE = re.compile(r"^\s*def\s+[A-Za-z_]\w*\s*\((.*)\)\s*(?:->\s*(.*))?:\s*$")
 
 
-def run_git_diff(repo: str) -> Tuple[int, str, str]:
+def run_git_diff(repo: str, base_ref: Optional[str] = None) -> Tuple[int, str, str]:
     cmd = [
         "git",
         "-C",
@@ -30,6 +30,8 @@ def run_git_diff(repo: str) -> Tuple[int, str, str]:
         "-w",
         "--ignore-blank-lines",
     ]
+    if base_ref:
+        cmd.append(base_ref)
     proc = subprocess.run(cmd, stdout=subprocess.PIPE, stderr=subprocess.PIPE, text=True)
     return proc.returncode, proc.stdout, proc.stderr
 
@@ -428,16 +430,42 @@ def save_parent_functions(parents: List[str]):
         json.dump(parents, f, indent=2)
 
 
+def list_changed_names(repo_root: str, changed_funcs: Dict[str, Set[str]]) -> List[Dict]:
+    """Ids and def lines of changed functions, without extracting bodies or writing functions.json."""
+    listed = []
+    for rel_file, funcs in sorted(changed_funcs.items()):
+        lines = {}
+        try:
+            for lineno, line in enumerate(Path(repo_root, rel_file).read_text().splitlines(), start=1):
+                m = PY_FUNC_DEF.match(line)
+                if m and m.group(1) in funcs:
+                    lines.setdefault(m.group(1), lineno)
+        except OSError:
+            pass
+        for fn in sorted(funcs):
+            listed.append({
+                "entry_full_id": make_full_id(rel_file, fn),
+                "file": rel_file,
+                "line": lines.get(fn)
+            })
+    return listed
+
+
 # ----------------- main ----------------- #
 def main(argv: Optional[List[str]] = None):
     p = argparse.ArgumentParser()
     p.add_argument("--repo", required=False, default="/home/bimal/Documents/ucsd/research/code/trap", help="path to the git repo to analyze")
+    p.add_argument("--base_ref", required=False, default=None, help="diff against this ref instead of the index")
+    p.add_argument("--names-only", dest="names_only", action="store_true", help="only list changed function ids, skipping bodies and the call graph")
     args = p.parse_args(argv)
     repo_root = os.path.abspath(args.repo)
     try:
-        res = run_git_diff(repo_root)
+        res = run_git_diff(repo_root, args.base_ref)
         parsed = parse_diff(res[1])
         changed_funcs = find_changed_functions(parsed)
+        if args.names_only:
+            print(json.dumps({"functions": list_changed_names(repo_root, changed_funcs)}, indent=2))
+            return
         if not changed_funcs:
             print(json.dumps({"parents": []}, indent=2))
             return
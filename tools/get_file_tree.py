@@ -25,7 +25,7 @@ def git_status(path: Path):
     except Exception:
         return {}
 
-def build_tree(path: Path, git_status_dict):
+def build_tree(path: Path, git_status_dict, with_metadata=False):
     node = {
         "name": path.name,
         "path": str(path),
@@ -36,14 +36,21 @@ def build_tree(path: Path, git_status_dict):
     if path.is_file():
         rel_path = os.path.relpath(path, start=repo_root)
         node["git"] = git_status_dict.get(rel_path)
+        if with_metadata:
+            # One stat per file, which is what makes this slow on huge trees
+            stat = path.stat()
+            node["size"] = stat.st_size
+            node["mtime"] = int(stat.st_mtime)
 
     if path.is_dir():
         children = []
         for p in sorted(path.iterdir(), key=lambda x: (not x.is_dir(), x.name.lower())):
             if p.name.startswith(".") or p.name.startswith("__pycache__"):
                 continue
-            children.append(build_tree(p, git_status_dict))
+            children.append(build_tree(p, git_status_dict, with_metadata))
         node["children"] = children
+        if with_metadata:
+            node["child_count"] = len(children)
 
     return node
 
@@ -51,8 +58,10 @@ if __name__ == "__main__":
     import argparse
     parser = argparse.ArgumentParser()
     parser.add_argument("--root", required=False, default=".")
+    parser.add_argument("--with-metadata", action="store_true",
+                        help="Add size and mtime to files and child_count to folders")
     args = parser.parse_args()
     repo_root = Path(args.root).resolve()
     git_changes = git_status(repo_root)
-    tree = build_tree(repo_root, git_changes)
+    tree = build_tree(repo_root, git_changes, args.with_metadata)
     print(json.dumps(tree, indent=2))
@@ -1,12 +1,20 @@
 import argparse
+import ast
 import sys
 import os
 import json
+import random
 import importlib.util
 import types
 import traceback
 import threading
 import bdb
+import dis
+import collections
+import contextlib
+import functools
+import itertools
+import linecache
 import inspect
 from datetime import datetime
 
@@ -14,8 +22,9 @@ from datetime import datetime
 # Logging Setup
 # --------------------------
 
-# Create logs directory if it doesn't exist
-LOG_DIR = os.path.join(os.path.dirname(__file__), "..", "logs")
+# Create logs directory if it doesn't exist. Packaged builds run this from
+# read-only app resources and point FLOWLENS_LOG_DIR somewhere writable.
+LOG_DIR = os.environ.get("FLOWLENS_LOG_DIR") or os.path.join(os.path.dirname(__file__), "..", "logs")
 os.makedirs(LOG_DIR, exist_ok=True)
 
 # Log file path with timestamp
@@ -49,13 +58,33 @@ def log_exception(e, context=""):
 # Helpers
 # --------------------------
 
+# Stream events are written to; selected with --events-on (stdout by default)
+EVENTS_STREAM = sys.stderr
+
+def quiet_stdout():
+    """Send prints of the traced code to stderr while events use stdout, so they
+    can't interleave with the JSON. sys.stdout is process-wide, so this also
+    covers the debugger thread; events still go to the saved EVENTS_STREAM."""
+    if EVENTS_STREAM is sys.stdout:
+        return contextlib.redirect_stdout(sys.stderr)
+    return contextlib.nullcontext()
+
 def send_event(event_json):
-    # Only send_event writes to stderr (for Rust communication)
+    # Only send_event writes to the events stream (for Rust communication)
     # All other output goes to log file
-    print(json.dumps(event_json, separators=(",", ":")), flush=True, file=sys.stderr)
+    print(json.dumps(event_json, separators=(",", ":")), flush=True, file=EVENTS_STREAM)
     log(f"Sent event: {json.dumps(event_json, separators=(',', ':'))[:200]}...")  # Log first 200 chars
 
 
+# Written after the last event of every step (the initial stop, each continue
+# and each JSON command) so Rust knows nothing more is coming for that step.
+STEP_END = {"event": "step_end"}
+
+
+def end_step():
+    send_event(STEP_END)
+
+
 def import_module_from_path(repo_root: str, rel_path: str):
     rel_path = rel_path.lstrip("/")
     abs_path = os.path.join(repo_root, rel_path)
@@ -85,7 +114,650 @@ def safe_json(value):
         return f"<unserializable {type(value).__name__}>"
 
 
-def get_function_signature(repo_root: str, entry_full_id: str):
+def get_entry_kind(func):
+    """Classify how a function has to be driven: called, iterated or awaited."""
+    if inspect.isasyncgenfunction(func):
+        return "async_generator"
+    if inspect.iscoroutinefunction(func):
+        return "async"
+    if inspect.isgeneratorfunction(func):
+        return "generator"
+    return "function"
+
+
+def drive_entry(fn, entry_kind):
+    """Wrap fn so calling the wrapper runs it to completion according to entry_kind."""
+    if entry_kind == "generator":
+        # Exhaust the generator so every yield point executes
+        def run_generator(*args, **kwargs):
+            return list(fn(*args, **kwargs))
+        return run_generator
+
+    if entry_kind == "async":
+        import asyncio
+
+        if inspect.isasyncgenfunction(fn):
+            async def collect(*args, **kwargs):
+                return [item async for item in fn(*args, **kwargs)]
+
+            def run_async_generator(*args, **kwargs):
+                return asyncio.run(collect(*args, **kwargs))
+            return run_async_generator
+
+        def run_coroutine(*args, **kwargs):
+            return asyncio.run(fn(*args, **kwargs))
+        return run_coroutine
+
+    return fn
+
+
+def find_function_node(module_path: str, fn_name: str):
+    """Return the AST node of a top-level function, or None if it isn't defined there."""
+    with open(module_path, "r", encoding="utf-8") as f:
+        tree = ast.parse(f.read(), filename=module_path)
+    for node in tree.body:
+        if isinstance(node, (ast.FunctionDef, ast.AsyncFunctionDef)) and node.name == fn_name:
+            return node
+    return None
+
+
+def get_decorators(module_path: str, fn_name: str):
+    """Return the source text of each decorator on a top-level function, outermost first."""
+    node = find_function_node(module_path, fn_name)
+    if node is None:
+        return []
+    return [ast.unparse(d) for d in node.decorator_list]
+
+
+def get_complexity(module_path: str, fn_name: str):
+    """Count branches, loops and boolean operators in a top-level function's AST."""
+    node = find_function_node(module_path, fn_name)
+    if node is None:
+        return None
+    branches = loops = boolean_ops = 0
+    for child in ast.walk(node):
+        if isinstance(child, (ast.If, ast.IfExp, ast.ExceptHandler)):
+            branches += 1
+        elif hasattr(ast, "match_case") and isinstance(child, ast.match_case):
+            branches += 1
+        elif isinstance(child, (ast.For, ast.AsyncFor, ast.While, ast.comprehension)):
+            loops += 1
+        elif isinstance(child, ast.BoolOp):
+            boolean_ops += len(child.values) - 1
+    return {
+        "branches": branches,
+        "loops": loops,
+        "boolean_ops": boolean_ops,
+        "line_count": node.end_lineno - node.lineno + 1,
+        "cyclomatic": 1 + branches + loops + boolean_ops
+    }
+
+
+def get_source_span(module_path: str, fn_name: str):
+    """Return (start_line, end_line) of a top-level function, decorators included."""
+    node = find_function_node(module_path, fn_name)
+    if node is None:
+        return None, None
+    start = min([node.lineno] + [d.lineno for d in node.decorator_list])
+    return start, node.end_lineno
+
+
+def repo_module_file(repo_root: str, module: str, current_file: str, level: int = 0):
+    """Path of the repo file an import refers to, or None for stdlib/third-party modules."""
+    if level:
+        base = os.path.dirname(current_file)
+        for _ in range(level - 1):
+            base = os.path.dirname(base)
+    else:
+        base = repo_root
+    candidate = os.path.join(base, *module.split(".")) if module else base
+    for path in (candidate + ".py", os.path.join(candidate, "__init__.py")):
+        if os.path.isfile(path):
+            return path
+    return None
+
+
+@functools.lru_cache(maxsize=None)
+def top_level_functions(module_path: str):
+    """Names of the functions defined at the top level of a file."""
+    with open(module_path, "r", encoding="utf-8") as f:
+        tree = ast.parse(f.read(), filename=module_path)
+    return {n.name for n in tree.body if isinstance(n, (ast.FunctionDef, ast.AsyncFunctionDef))}
+
+
+def module_bindings(repo_root: str, module_path: str, tree):
+    """What module-level imports refer to in the repo.
+
+    Returns (names, modules): names maps a local name to (file, name in that
+    file) and modules maps a module alias to its file.
+    """
+    imported_names = {}
+    imported_modules = {}
+    for stmt in tree.body:
+        if isinstance(stmt, ast.Import):
+            for alias in stmt.names:
+                path = repo_module_file(repo_root, alias.name, module_path)
+                if path and alias.asname:
+                    imported_modules[alias.asname] = path
+        elif isinstance(stmt, ast.ImportFrom):
+            module_file = repo_module_file(repo_root, stmt.module or "", module_path, stmt.level)
+            for alias in stmt.names:
+                local = alias.asname or alias.name
+                submodule = repo_module_file(repo_root, alias.name, module_file or "", 1) if module_file else None
+                if submodule and os.path.basename(module_file) == "__init__.py":
+                    imported_modules[local] = submodule
+                elif module_file:
+                    imported_names[local] = (module_file, alias.name)
+    return imported_names, imported_modules
+
+
+def resolved_calls(repo_root: str, module_path: str, node, bindings):
+    """Yield (call node, entry_full_id or None) for every call inside node.
+
+    The id is set only when the callee is statically a top-level function of a
+    repo file, defined in the same module or imported from one.
+    """
+    imported_names, imported_modules = bindings
+
+    def resolve(path, name):
+        try:
+            if name in top_level_functions(path):
+                return "/" + os.path.relpath(path, repo_root).replace("\\", "/") + "::" + name
+        except (OSError, SyntaxError):
+            pass
+        return None
+
+    for child in ast.walk(node):
+        if not isinstance(child, ast.Call):
+            continue
+        func = child.func
+        resolved = None
+        if isinstance(func, ast.Name):
+            if func.id in imported_names:
+                resolved = resolve(*imported_names[func.id])
+            else:
+                resolved = resolve(module_path, func.id)
+        elif isinstance(func, ast.Attribute) and isinstance(func.value, ast.Name) and func.value.id in imported_modules:
+            resolved = resolve(imported_modules[func.value.id], func.attr)
+        yield child, resolved
+
+
+def get_callees(repo_root: str, entry_full_id: str):
+    """List calls made in a top-level function's body, in source order.
+
+    Each call keeps its source text as `name`. Dynamic calls such as
+    `handlers[kind](x)` or methods on local objects are listed unresolved.
+    """
+    rel_path, fn_name = entry_full_id.split("::", 1)
+    module_path = os.path.join(repo_root, rel_path.lstrip("/"))
+    with open(module_path, "r", encoding="utf-8") as f:
+        tree = ast.parse(f.read(), filename=module_path)
+    node = next((n for n in tree.body
+                 if isinstance(n, (ast.FunctionDef, ast.AsyncFunctionDef)) and n.name == fn_name), None)
+    if node is None:
+        return {"error": f"function {fn_name} not found"}
+
+    callees = []
+    seen = set()
+    for call, resolved in resolved_calls(repo_root, module_path, node, module_bindings(repo_root, module_path, tree)):
+        name = ast.unparse(call.func)
+        if (name, resolved) in seen:
+            continue
+        seen.add((name, resolved))
+        callees.append({"name": name, "entry_full_id": resolved, "line": call.lineno})
+
+    callees.sort(key=lambda c: c["line"])
+    return {"callees": callees}
+
+
+def get_cfg(repo_root: str, entry_full_id: str):
+    """Map each statement line of a top-level function to the lines that can run next.
+
+    Statement-level and static: an if lists both arms, a loop header its body
+    and the code after the loop, the end of a loop body goes back to the
+    header, and return/raise (or falling off the end) lead nowhere. Exceptions
+    are only modelled as try -> except edges. Each line also lists the calls
+    it makes, resolved like get_callees.
+    """
+    rel_path, fn_name = entry_full_id.split("::", 1)
+    module_path = os.path.join(repo_root, rel_path.lstrip("/"))
+    with open(module_path, "r", encoding="utf-8") as f:
+        tree = ast.parse(f.read(), filename=module_path)
+    node = next((n for n in tree.body
+                 if isinstance(n, (ast.FunctionDef, ast.AsyncFunctionDef)) and n.name == fn_name), None)
+    if node is None:
+        return {"error": f"function {fn_name} not found"}
+    bindings = module_bindings(repo_root, module_path, tree)
+    lines = {}
+
+    def entry(line):
+        return lines.setdefault(line, {"successors": [], "calls": []})
+
+    def add(line, target, kind):
+        successors = entry(line)["successors"]
+        if target is not None and not any(s["line"] == target for s in successors):
+            successors.append({"line": target, "kind": kind})
+
+    def add_calls(line, expr):
+        for call, resolved in resolved_calls(repo_root, module_path, expr, bindings):
+            entry(line)["calls"].append({"name": ast.unparse(call.func), "entry_full_id": resolved})
+
+    def first(stmts, follow):
+        return stmts[0].lineno if stmts else follow
+
+    def walk(stmts, follow, loop):
+        """loop is (header line, line after the loop) of the innermost loop, or None."""
+        for i, stmt in enumerate(stmts):
+            line = stmt.lineno
+            nxt = stmts[i + 1].lineno if i + 1 < len(stmts) else follow
+            entry(line)
+            if isinstance(stmt, ast.If):
+                add_calls(line, stmt.test)
+                add(line, stmt.body[0].lineno, "branch")
+                add(line, first(stmt.orelse, nxt), "branch")
+                walk(stmt.body, nxt, loop)
+                walk(stmt.orelse, nxt, loop)
+            elif isinstance(stmt, (ast.For, ast.AsyncFor, ast.While)):
+                add_calls(line, stmt.test if isinstance(stmt, ast.While) else stmt.iter)
+                add(line, stmt.body[0].lineno, "loop_body")
+                add(line, first(stmt.orelse, nxt), "loop_exit")
+                walk(stmt.body, line, (line, nxt))
+                walk(stmt.orelse, nxt, loop)
+            elif isinstance(stmt, (ast.With, ast.AsyncWith)):
+                for item in stmt.items:
+                    add_calls(line, item.context_expr)
+                add(line, stmt.body[0].lineno, "next")
+                walk(stmt.body, nxt, loop)
+            elif isinstance(stmt, ast.Try) or type(stmt).__name__ == "TryStar":
+                after = first(stmt.finalbody, nxt)
+                add(line, stmt.body[0].lineno, "next")
+                walk(stmt.body, first(stmt.orelse, after), loop)
+                for handler in stmt.handlers:
+                    add(line, handler.body[0].lineno, "handler")
+                    walk(handler.body, after, loop)
+                walk(stmt.orelse, after, loop)
+                walk(stmt.finalbody, nxt, loop)
+            elif type(stmt).__name__ == "Match":
+                add_calls(line, stmt.subject)
+                for case in stmt.cases:
+                    add(line, case.body[0].lineno, "branch")
+                    walk(case.body, nxt, loop)
+                add(line, nxt, "branch")
+            elif isinstance(stmt, ast.Break):
+                add(line, loop[1] if loop else None, "loop_exit")
+            elif isinstance(stmt, ast.Continue):
+                add(line, loop[0] if loop else None, "loop_back")
+            elif isinstance(stmt, (ast.Return, ast.Raise)):
+                add_calls(line, stmt)
+            elif isinstance(stmt, (ast.FunctionDef, ast.AsyncFunctionDef, ast.ClassDef)):
+                # Only the definition runs here, not the body
+                add(line, nxt, "next")
+            else:
+                add_calls(line, stmt)
+                add(line, nxt, "loop_back" if loop and nxt == loop[0] else "next")
+
+    walk(node.body, None, None)
+    return {"lines": {str(line): info for line, info in sorted(lines.items())}}
+
+
+# Modules whose use means a function touches files, the network or other processes
+IO_MODULES = {
+    "os", "io", "shutil", "pathlib", "tempfile", "glob", "socket", "ssl", "http",
+    "urllib", "requests", "httpx", "aiohttp", "subprocess", "sqlite3", "smtplib", "ftplib"
+}
+# Builtins that do I/O on their own
+IO_BUILTINS = {"open", "input"}
+
+
+def get_source(repo_root: str, entry_full_id: str):
+    """Source of a top-level function, decorators included, and the lines a breakpoint can stop on.
+
+    Valid lines are the first lines of the statements in its body (nested ones
+    included), since the tracer only pauses where a statement starts.
+    """
+    rel_path, fn_name = entry_full_id.split("::", 1)
+    module_path = os.path.join(repo_root, rel_path.lstrip("/"))
+    node = find_function_node(module_path, fn_name)
+    if node is None:
+        return {"error": f"function {fn_name} not found"}
+    start, end = get_source_span(module_path, fn_name)
+    with open(module_path, "r", encoding="utf-8") as f:
+        source = f.read().splitlines()[start - 1:end]
+    valid_lines = sorted({child.lineno for child in ast.walk(node) if isinstance(child, ast.stmt) and child is not node})
+    return {
+        "file": rel_path.lstrip("/"),
+        "start_line": start,
+        "end_line": end,
+        "source": "\n".join(source),
+        "valid_lines": valid_lines,
+    }
+
+
+def analyze_function(repo_root: str, entry_full_id: str):
+    """Static hints about how expensive or side-effecting tracing a top-level function may be.
+
+    Reports its length, loops (and how deeply they nest), whether it calls
+    itself, and I/O: calls to open()/input() or into I/O modules the file
+    imports. Nothing is imported or run.
+    """
+    rel_path, fn_name = entry_full_id.split("::", 1)
+    module_path = os.path.join(repo_root, rel_path.lstrip("/"))
+    with open(module_path, "r", encoding="utf-8") as f:
+        tree = ast.parse(f.read(), filename=module_path)
+    node = next((n for n in tree.body
+                 if isinstance(n, (ast.FunctionDef, ast.AsyncFunctionDef)) and n.name == fn_name), None)
+    if node is None:
+        return {"error": f"function {fn_name} not found"}
+
+    # Local name -> top-level module, for imports of I/O modules anywhere in the file
+    io_names = {}
+    for imp in ast.walk(tree):
+        if isinstance(imp, ast.Import):
+            for alias in imp.names:
+                if alias.name.split(".")[0] in IO_MODULES:
+                    io_names[alias.asname or alias.name.split(".")[0]] = alias.name.split(".")[0]
+        elif isinstance(imp, ast.ImportFrom) and imp.module and not imp.level:
+            if imp.module.split(".")[0] in IO_MODULES:
+                for alias in imp.names:
+                    io_names[alias.asname or alias.name] = imp.module.split(".")[0]
+
+    loops = 0
+    max_depth = 0
+    recursive = False
+    io_calls = []
+
+    def visit(n, depth):
+        nonlocal loops, max_depth, recursive
+        if isinstance(n, (ast.For, ast.AsyncFor, ast.While)):
+            loops += 1
+            depth += 1
+            max_depth = max(max_depth, depth)
+        elif isinstance(n, ast.Call):
+            root = n.func
+            while isinstance(root, ast.Attribute):
+                root = root.value
+            if isinstance(n.func, ast.Name) and n.func.id == fn_name:
+                recursive = True
+            if isinstance(root, ast.Name) and (root.id in io_names or (root is n.func and root.id in IO_BUILTINS)):
+                io_calls.append({"name": ast.unparse(n.func), "line": n.lineno, "module": io_names.get(root.id)})
+        for child in ast.iter_child_nodes(n):
+            # Nested definitions don't run as part of this call
+            if not isinstance(child, (ast.FunctionDef, ast.AsyncFunctionDef, ast.ClassDef, ast.Lambda)):
+                visit(child, depth)
+
+    for stmt in node.body:
+        visit(stmt, 0)
+    return {
+        "line_count": node.end_lineno - node.lineno + 1,
+        "loops": loops,
+        "max_loop_depth": max_depth,
+        "recursive": recursive,
+        "io_calls": io_calls,
+        "io_modules": sorted({call["module"] for call in io_calls if call["module"]})
+    }
+
+
+def get_callers(repo_root: str, entry_full_id: str):
+    """Top-level functions anywhere in the repo with a call that statically resolves to entry_full_id."""
+    rel_path, fn_name = entry_full_id.split("::", 1)
+    target = "/" + rel_path.lstrip("/") + "::" + fn_name
+    repo_root = os.path.abspath(repo_root)
+
+    callers = []
+    for root, dirs, files in os.walk(repo_root):
+        dirs[:] = [d for d in dirs if not d.startswith(".") and d not in ("venv", "__pycache__", "node_modules")]
+        for fname in files:
+            if not fname.endswith(".py"):
+                continue
+            module_path = os.path.join(root, fname)
+            try:
+                with open(module_path, "r", encoding="utf-8") as f:
+                    tree = ast.parse(f.read(), filename=module_path)
+            except (OSError, SyntaxError, UnicodeDecodeError):
+                continue
+            bindings = module_bindings(repo_root, module_path, tree)
+            rel = "/" + os.path.relpath(module_path, repo_root).replace("\\", "/")
+            for node in tree.body:
+                if not isinstance(node, (ast.FunctionDef, ast.AsyncFunctionDef)):
+                    continue
+                if any(resolved == target for _, resolved in resolved_calls(repo_root, module_path, node, bindings)):
+                    callers.append(f"{rel}::{node.name}")
+
+    return {"callers": sorted(callers)}
+
+
+def short_repr(value, limit=200):
+    try:
+        text = repr(value)
+    except Exception:
+        return f"<unrepresentable {type(value).__name__}>"
+    return text if len(text) <= limit else text[:limit] + "..."
+
+
+def receiver_of(frame):
+    """The self/cls a method frame runs on, as {kind, type, repr}, or None for plain functions."""
+    code = frame.f_code
+    if code.co_argcount == 0 or code.co_varnames[0] not in ("self", "cls"):
+        return None
+    kind = code.co_varnames[0]
+    if kind not in frame.f_locals:
+        return None
+    value = frame.f_locals[kind]
+    type_name = value.__name__ if kind == "cls" and isinstance(value, type) else type(value).__name__
+    return {"kind": kind, "type": type_name, "repr": short_repr(value)}
+
+
+def is_expandable(value):
+    if isinstance(value, (dict, list, tuple, set, frozenset)):
+        return len(value) > 0
+    if isinstance(value, (types.FunctionType, types.ModuleType, type)):
+        return False
+    return bool(getattr(value, "__dict__", None))
+
+
+def iter_children(value):
+    """Yield (name, child) pairs of a container or object."""
+    if isinstance(value, dict):
+        yield from ((str(k), v) for k, v in value.items())
+    elif isinstance(value, (list, tuple)):
+        yield from ((str(i), v) for i, v in enumerate(value))
+    elif isinstance(value, (set, frozenset)):
+        yield from ((str(i), v) for i, v in enumerate(value))
+    elif is_expandable(value):
+        yield from vars(value).items()
+
+
+def describe_value(name, value):
+    return {
+        "name": name,
+        "type": type(value).__name__,
+        "repr": short_repr(value),
+        "expandable": is_expandable(value)
+    }
+
+
+def resolve_variable_path(frame, path):
+    """Follow path (local name, then keys/indices/attributes) from a paused frame."""
+    if not path:
+        raise KeyError("empty path")
+    name = path[0]
+    if name in frame.f_locals:
+        value = frame.f_locals[name]
+    elif name in frame.f_globals:
+        value = frame.f_globals[name]
+    else:
+        raise KeyError(name)
+    for segment in path[1:]:
+        if isinstance(value, dict):
+            matches = [k for k in value if str(k) == segment]
+            if not matches:
+                raise KeyError(segment)
+            value = value[matches[0]]
+        elif isinstance(value, (list, tuple)):
+            value = value[int(segment)]
+        elif isinstance(value, (set, frozenset)):
+            value = list(value)[int(segment)]
+        else:
+            value = getattr(value, segment)
+    return value
+
+
+def handle_command(dbg, command):
+    """Answer a JSON command sent while paused. Always returns an event so Rust never waits forever."""
+    cmd = command.get("cmd")
+    if cmd == "expand":
+        return dbg.expand(command.get("path", []), command.get("limit", 100))
+    if cmd == "slice":
+        return dbg.slice(command.get("path", []), command.get("start", 0), command.get("count", 100))
+    if cmd == "breakpoints":
+        dbg.breakpoints = parse_breakpoints(dbg.repo_root, command.get("spec", ""))
+        log(f"Breakpoints replaced: {sorted(dbg.breakpoints)}")
+        return {
+            "event": "breakpoints",
+            "breakpoints": [
+                {"file": os.path.relpath(file, dbg.repo_root).replace("\\", "/"), "line": line}
+                for file, line in sorted(dbg.breakpoints)
+            ]
+        }
+    return {"event": "error", "error": f"unknown command: {cmd}"}
+
+
+def run_to_end(fn, args, kwargs, repo_root, profile=False):
+    """Run fn to completion without pausing, counting executed lines of repo code.
+
+    With profile, the end event also carries line_counts: how often each
+    "file:line" ran, keyed by repo-relative file; and executable_lines: per
+    file, the lines of every function that ran, so unexecuted ones show up too.
+    """
+    repo_root = os.path.abspath(repo_root)
+    steps = 0
+    counts = collections.Counter()
+    codes = set()
+
+    def count_lines(frame, event, arg):
+        nonlocal steps
+        if event == "line":
+            steps += 1
+            if profile:
+                counts[(frame.f_code.co_filename, frame.f_lineno)] += 1
+        return count_lines
+
+    def trace_calls(frame, event, arg):
+        # Only descend into frames of repo code; library internals aren't steps
+        filename = frame.f_code.co_filename
+        if not filename.startswith("<") and os.path.abspath(filename).startswith(repo_root):
+            if profile:
+                codes.add(frame.f_code)
+            return count_lines
+        return None
+
+    sys.settrace(trace_calls)
+    try:
+        with quiet_stdout():
+            value = fn(*args, **kwargs)
+    except Exception as e:
+        sys.settrace(None)
+        log_exception(e, "run_to_end")
+        end_event = {
+            "event": "end",
+            "exception": {"type": type(e).__name__, "message": str(e), "traceback": traceback.format_exc()},
+            "steps": steps
+        }
+    else:
+        sys.settrace(None)
+        end_event = {"event": "end", "return_value": safe_json(value), "steps": steps}
+    if profile:
+        end_event["line_counts"] = {
+            f"{os.path.relpath(os.path.abspath(filename), repo_root)}:{line}": n
+            for (filename, line), n in sorted(counts.items())
+        }
+        executable = collections.defaultdict(set)
+        for code in codes:
+            executable[os.path.relpath(os.path.abspath(code.co_filename), repo_root)] |= code_lines(code)
+        end_event["executable_lines"] = {file: sorted(lines) for file, lines in sorted(executable.items())}
+    return end_event
+
+
+def code_lines(code) -> set:
+    """Lines of code's body that can run. The def line is left out: it only runs when the function is defined."""
+    return {
+        line for _, line in dis.findlinestarts(code)
+        if line is not None and line != code.co_firstlineno
+    }
+
+
+def source_context(filename, line, radius):
+    """Up to radius source lines either side of line, as {start, lines}; None when the file can't be read."""
+    start = max(1, line - radius)
+    lines = [linecache.getline(filename, n).rstrip("\n") for n in range(start, line + radius + 1)]
+    # getline gives "" past the end of the file, but blank lines inside it are "\n"
+    while lines and not linecache.getline(filename, start + len(lines) - 1):
+        lines.pop()
+    return {"start": start, "lines": lines} if lines else None
+
+
+def seed_random(seed: int):
+    """Seed random, and numpy.random if the traced code imported numpy, so reruns make the same choices."""
+    random.seed(seed)
+    numpy = sys.modules.get("numpy")
+    if numpy is not None:
+        numpy.random.seed(seed % 2**32)
+    log(f"Seeded random with {seed}{' (and numpy.random)' if numpy is not None else ''}")
+
+
+def parse_breakpoints(repo_root: str, spec: str):
+    """Turn "a.py:10,pkg/b.py:4" into {(absolute path, line)}."""
+    breakpoints = set()
+    for item in filter(None, (part.strip() for part in spec.split(","))):
+        file, _, line = item.rpartition(":")
+        breakpoints.add((os.path.abspath(os.path.join(repo_root, file.lstrip("/"))), int(line)))
+    return breakpoints
+
+
+def parse_args_json(args_json: str):
+    """Split {"args": [...], "kwargs": {...}} into call arguments, ignoring malformed input."""
+    if args_json:
+        try:
+            parsed = json.loads(args_json)
+            return parsed.get("args", []), parsed.get("kwargs", {})
+        except Exception:
+            pass
+    return [], {}
+
+
+def build_args_from_setup(module, setup_expr: str):
+    """Evaluate setup_expr in the entry module's namespace and turn the result into call arguments.
+
+    A tuple becomes positional args, a dict becomes kwargs, anything else is passed
+    as the single positional argument.
+    """
+    value = eval(setup_expr, vars(module))
+    if isinstance(value, tuple):
+        return list(value), {}
+    if isinstance(value, dict):
+        return [], value
+    return [value], {}
+
+
+def check_importable(repo_root: str, entry_full_id: str):
+    """Import the entry's module, without calling anything, and report whether that worked."""
+    rel_path = entry_full_id.split("::", 1)[0]
+    try:
+        # Keep prints at import time out of the JSON answer on stdout
+        with contextlib.redirect_stdout(sys.stderr):
+            import_module_from_path(repo_root, rel_path)
+    except BaseException as e:
+        # SystemExit and KeyboardInterrupt raised at import time are failures too
+        log_exception(e, "check_importable")
+        return {
+            "importable": False,
+            "error": f"{type(e).__name__}: {e}",
+            "traceback": traceback.format_exc()
+        }
+    return {"importable": True}
+
+
+def get_function_signature(repo_root: str, entry_full_id: str, with_decorators: bool = False, with_complexity: bool = False):
     """Get the function signature (parameter names) for a given function."""
     try:
         log(f"get_function_signature called: repo_root={repo_root}, entry_full_id={entry_full_id}")
@@ -108,12 +780,25 @@ def get_function_signature(repo_root: str, entry_full_id: str):
         
         sig = inspect.signature(func)
         params = list(sig.parameters.keys())
-        log(f"Function signature: params={params}, param_count={len(params)}")
+        kind = get_entry_kind(func)
+        log(f"Function signature: params={params}, param_count={len(params)}, kind={kind}")
         
-        return {
+        start_line, end_line = get_source_span(module.__file__, fn_name)
+        result = {
             "params": params,
-            "param_count": len(params)
+            "param_count": len(params),
+            "kind": kind,
+            "file": rel_path.lstrip("/"),
+            "start_line": start_line,
+            "end_line": end_line
         }
+        if with_decorators:
+            result["decorators"] = get_decorators(module.__file__, fn_name)
+            log(f"Function decorators: {result['decorators']}")
+        if with_complexity:
+            result["complexity"] = get_complexity(module.__file__, fn_name)
+            log(f"Function complexity: {result['complexity']}")
+        return result
     except Exception as e:
         log_exception(e, "get_function_signature")
         return {"error": str(e)}
@@ -131,13 +816,43 @@ class PersistentDebugger(bdb.Bdb):
         self.running_thread = None
         self.target_file = None
         self.thread_exception = None  # Store exceptions from the debugger thread
+        self.current_frame = None  # Frame paused at the last event, for inspection commands
+        self.run_to_function = None  # Function name to pause on entry to, set by run_to
+        self.breakpoints = set()  # (absolute file, line) pairs to pause at in any file
+        self.finished = False  # The entry function has returned; continuing just repeats the last event
+        self.finish_frame = None  # Frame that finish is waiting to return
+        self.finish_caller = None  # Its caller, where finish pauses next
+        self.finish_result = None  # Function name and return value of finish_frame, once it returned
+        self.over_frame = None  # Frame step_over pauses at the next line of; its caller once it returns
+        self.step_scope = "project"  # "project": library frames are not traced at all; "all": every frame is
+        self.context_lines = 0  # Source lines either side of the paused line each event carries; 0 for none
+
+    def in_project(self, frame):
+        """Whether frame runs repo code; installed packages count as library code even inside the repo (e.g. a .venv)."""
+        filename = frame.f_code.co_filename
+        if filename.startswith("<"):
+            return False
+        path = os.path.realpath(filename)
+        root = os.path.realpath(self.repo_root)
+        parts = path.split(os.sep)
+        return path.startswith(root + os.sep) and "site-packages" not in parts and "dist-packages" not in parts
+
+    def dispatch_call(self, frame, arg):
+        # In project scope library frames get no line, return or exception
+        # events; repo code they call back into is still traced
+        if self.step_scope == "project" and self.botframe is not None and not self.in_project(frame):
+            return None
+        return super().dispatch_call(frame, arg)
 
     def user_line(self, frame):
         lineno = frame.f_lineno
         fname = os.path.abspath(frame.f_code.co_filename)
         log(f"user_line called: line {lineno} in {fname}")
-        # Only stop for the main target file
-        if fname != self.target_file:
+        at_breakpoint = (fname, lineno) in self.breakpoints
+        finishing = self.finish_result is not None and frame is self.finish_caller
+        stepping_over = frame is self.over_frame
+        # Only stop for the main target file, or at a breakpoint in any file
+        if fname != self.target_file and not at_breakpoint and not finishing and not stepping_over:
             log(f"Skipping line {lineno} (not in target file {self.target_file})")
             return
 
@@ -180,38 +895,227 @@ class PersistentDebugger(bdb.Bdb):
 
         self.last_event = {
             "event": "line",
+            "phase": "line",
             "filename": fname,
             "function": funcname,
             "line": lineno,
             "locals": locals_snapshot,
+            "receiver": receiver_of(frame),
             "globals": globals_snapshot
         }
         log(f"Created line event: {funcname}:{lineno}, target_line={self.target_line}")
 
+        if at_breakpoint:
+            log(f"Hit breakpoint {fname}:{lineno}, stopping and waiting")
+            self.last_event["breakpoint"] = {
+                "file": os.path.relpath(fname, self.repo_root).replace("\\", "/"),
+                "line": lineno
+            }
+            self.pause(frame)
+        elif finishing:
+            log(f"Back in caller {funcname}:{lineno} after finish, stopping and waiting")
+            self.last_event.update(self.finish_result)
+            self.last_event["stack"] = self.call_stack(frame)
+            self.finish_caller = None
+            self.finish_result = None
+            self.pause(frame)
+        elif stepping_over:
+            log(f"Stepped over to {funcname}:{lineno}, stopping and waiting")
+            self.last_event["stack"] = self.call_stack(frame)
+            self.over_frame = None
+            self.pause(frame)
         # Stop if we've reached the target line
-        if self.target_line is not None and lineno >= self.target_line:
+        elif self.target_line is not None and fname == self.target_file and lineno >= self.target_line:
             log(f"Reached target line {self.target_line} (current: {lineno}), stopping and waiting")
-            self.set_step()
-            # Notify main thread that we have a fresh event ready
-            self.ready_event.set()
-            log("Set ready_event, waiting for step_event")
-            # Wait until the main thread asks us to continue
-            self.step_event.clear()
-            self.step_event.wait()
-            log("Received step_event, continuing")
+            self.pause(frame)
+
+    def user_call(self, frame, argument_list):
+        """Called when a function is entered; pauses there while running to a named function."""
+        if self.run_to_function is None or frame.f_code.co_name != self.run_to_function:
+            return
+        log(f"Entered {self.run_to_function}, stopping and waiting")
+        self.run_to_function = None
+        self.last_event = {
+            "event": "call",
+            "phase": "call",
+            "filename": os.path.abspath(frame.f_code.co_filename),
+            "function": frame.f_code.co_name,
+            "line": frame.f_lineno,
+            "locals": {k: safe_json(v) for k, v in frame.f_locals.items()},
+            "receiver": receiver_of(frame),
+            "stack": self.call_stack(frame),
+            "reached": True
+        }
+        self.pause(frame)
+
+    def user_exception(self, frame, exc_info):
+        """Called when an exception is raised or propagates; pauses where one is raised in the target file,
+        or where one reaches the frame being stepped over."""
+        exc_type, exc_value, tb = exc_info
+        fname = os.path.abspath(frame.f_code.co_filename)
+        stepping_over = frame is self.over_frame
+        # Further up the stack the traceback has grown, so tb_next is only None where it was raised
+        raised_here = tb is None or tb.tb_next is None
+        if not stepping_over and (self.target_line is None or fname != self.target_file or not raised_here):
+            return
+        self.over_frame = None
+        log(f"Exception {exc_type.__name__} raised at {fname}:{frame.f_lineno}, stopping and waiting")
+        self.last_event = {
+            "event": "exception",
+            "phase": "exception",
+            "filename": fname,
+            "function": frame.f_code.co_name,
+            "line": frame.f_lineno,
+            "locals": {k: safe_json(v) for k, v in frame.f_locals.items()},
+            "receiver": receiver_of(frame),
+            "exception": {"type": exc_type.__name__, "message": str(exc_value)}
+        }
+        self.pause(frame)
+
+    def call_stack(self, frame):
+        """Frames from the entry function down to frame, skipping debugger and wrapper frames."""
+        repo_root = os.path.abspath(self.repo_root)
+        stack = []
+        while frame is not None:
+            fname = os.path.abspath(frame.f_code.co_filename)
+            # "<string>" is the debugger's runctx frame, not user code
+            if not frame.f_code.co_filename.startswith("<") and fname.startswith(repo_root):
+                stack.append({"function": frame.f_code.co_name, "filename": fname, "line": frame.f_lineno})
+            frame = frame.f_back
+        return list(reversed(stack))
+
+    def pause(self, frame):
+        """Hand last_event to the main thread and block until it asks us to continue."""
+        self.current_frame = frame
+        if self.context_lines and self.last_event.get("line"):
+            self.last_event["context"] = source_context(self.last_event["filename"], self.last_event["line"], self.context_lines)
+        self.set_step()
+        # Notify main thread that we have a fresh event ready
+        self.ready_event.set()
+        log("Set ready_event, waiting for step_event")
+        # Wait until the main thread asks us to continue
+        self.step_event.clear()
+        self.step_event.wait()
+        log("Received step_event, continuing")
+
+    def expand(self, path, limit):
+        """Describe the value at path in the paused frame along with up to limit children."""
+        if self.current_frame is None:
+            return {"event": "error", "error": "tracer is not paused in a frame"}
+        try:
+            value = resolve_variable_path(self.current_frame, path)
+        except Exception as e:
+            return {"event": "error", "error": f"cannot resolve {'.'.join(path)}: {e}"}
+        children = list(iter_children(value))
+        return {
+            "event": "expand",
+            "path": path,
+            "type": type(value).__name__,
+            "repr": short_repr(value),
+            "children": [describe_value(name, child) for name, child in children[:limit]],
+            "total": len(children),
+            "truncated": len(children) > limit
+        }
+
+    def slice(self, path, start, count):
+        """Describe items start..start+count of the list, tuple, set or dict at path, without walking the rest."""
+        if self.current_frame is None:
+            return {"event": "error", "error": "tracer is not paused in a frame"}
+        try:
+            value = resolve_variable_path(self.current_frame, path)
+        except Exception as e:
+            return {"event": "error", "error": f"cannot resolve {'.'.join(path)}: {e}"}
+        if isinstance(value, dict):
+            window = ((str(k), v) for k, v in itertools.islice(value.items(), start, start + count))
+        elif isinstance(value, (list, tuple)):
+            window = ((str(start + i), v) for i, v in enumerate(value[start:start + count]))
+        elif isinstance(value, (set, frozenset)):
+            window = ((str(start + i), v) for i, v in enumerate(itertools.islice(value, start, start + count)))
+        else:
+            return {"event": "error", "error": f"{'.'.join(path)} is a {type(value).__name__}, not a collection"}
+        return {
+            "event": "slice",
+            "path": path,
+            "type": type(value).__name__,
+            "start": start,
+            "items": [describe_value(name, child) for name, child in window],
+            "total": len(value)
+        }
 
     def continue_until(self, line):
         log(f"continue_until called with line={line}")
         self.target_line = line
+        self.run_to_function = None
+        self.over_frame = None
+        if self.finished:
+            return
         self.ready_event.clear()
         self.step_event.set()
         log("Set step_event to continue execution")
 
+    def run_to(self, function_name):
+        log(f"run_to called with function={function_name}")
+        self.target_line = None
+        self.run_to_function = function_name
+        self.over_frame = None
+        if self.finished:
+            return
+        self.ready_event.clear()
+        self.step_event.set()
+
+    def finish(self):
+        """Continue until the paused frame returns, then pause at the next line of its caller."""
+        log("finish called")
+        if self.current_frame is None:
+            return False
+        self.target_line = None
+        self.run_to_function = None
+        self.over_frame = None
+        self.finish_frame = self.current_frame
+        self.finish_caller = self.caller_of(self.current_frame)
+        self.finish_result = None
+        if self.finished:
+            return True
+        self.ready_event.clear()
+        self.step_event.set()
+        return True
+
+    def step_over(self):
+        """Continue to the next line of the paused frame, running any calls it makes without pausing
+        (breakpoints aside); past its last line, pause at the next line of its caller."""
+        log("step_over called")
+        if self.current_frame is None:
+            return False
+        self.target_line = None
+        self.run_to_function = None
+        self.finish_frame = None
+        self.finish_caller = None
+        self.finish_result = None
+        self.over_frame = self.current_frame
+        if self.finished:
+            return True
+        self.ready_event.clear()
+        self.step_event.set()
+        return True
+
+    def caller_of(self, frame):
+        """Frame that frame returns to; in project scope, the nearest repo one, since library callers are never traced."""
+        caller = frame.f_back
+        while self.step_scope == "project" and caller is not None and not self.in_project(caller):
+            caller = caller.f_back
+        return caller
+
     def wait_for_event(self, timeout=None):
         return self.ready_event.wait(timeout=timeout)
 
     def user_return(self, frame, return_value):
         """Called when a function returns."""
+        if frame is self.finish_frame:
+            log(f"{frame.f_code.co_name} returned during finish")
+            self.finish_frame = None
+            self.finish_result = {"finished_function": frame.f_code.co_name, "return_value": safe_json(return_value)}
+        if frame is self.over_frame:
+            self.over_frame = self.caller_of(frame)
         # If function completes before reaching target line, create an event
         if self.target_line is not None and self.last_event is None:
             # Function completed before we could capture an event
@@ -220,10 +1124,12 @@ class PersistentDebugger(bdb.Bdb):
             if fname == self.target_file:
                 self.last_event = {
                     "event": "return",
+                    "phase": "return",
                     "filename": fname,
                     "function": frame.f_code.co_name,
                     "line": frame.f_lineno,
                     "locals": {k: safe_json(v) for k, v in frame.f_locals.items()},
+                    "receiver": receiver_of(frame),
                     "return_value": safe_json(return_value)
                 }
                 self.ready_event.set()
@@ -236,17 +1142,39 @@ class PersistentDebugger(bdb.Bdb):
         def run_with_error_handling():
             try:
                 log("Starting function execution in debugger thread")
-                self.runctx(
-                    "fn(*args, **kwargs)",
-                    globals={"fn": fn, "args": args, "kwargs": kwargs},
-                    locals={}
-                )
+                result = {}
+                with quiet_stdout():
+                    self.runctx(
+                        "result['value'] = fn(*args, **kwargs)",
+                        globals={"fn": fn, "args": args, "kwargs": kwargs, "result": result},
+                        locals={}
+                    )
                 log("Function execution completed normally")
                 # If we get here, function completed normally
+                self.finished = True
+                completion = {
+                    "event": "return",
+                    "phase": "return",
+                    "filename": self.target_file,
+                    "return_value": safe_json(result.get("value")),
+                    "completed": True
+                }
                 # Check if we need to set ready_event (in case function completed before target line)
-                if not self.ready_event.is_set() and self.target_line is not None:
-                    # Function completed but we never reached target line
+                if not self.ready_event.is_set() and self.run_to_function is not None:
+                    log(f"Function completed without entering {self.run_to_function}")
+                    completion["target_function"] = self.run_to_function
+                    completion["reached"] = False
+                    self.last_event = completion
+                    self.run_to_function = None
+                    self.ready_event.set()
+                elif not self.ready_event.is_set():
+                    # Function completed but we never reached target line, or finish left the entry
+                    # function (whose caller may be untraced, so finish_caller can already be None);
+                    # likewise when step_over ran past the end of it
+                    self.finish_caller = None
+                    self.over_frame = None
                     log("Function completed before reaching target line, setting ready_event")
+                    self.last_event = completion
                     self.ready_event.set()
             except Exception as e:
                 # Store the exception
@@ -288,32 +1216,175 @@ def main():
         required=False,
         default='{"kwargs": {"metric_name": "test", "period": "last_7_days"}}'
     )
+    parser.add_argument(
+        "--setup_expr",
+        required=False,
+        default=None,
+        help="Python expression evaluated in the entry module to build the call arguments"
+    )
+    parser.add_argument(
+        "--seed",
+        required=False,
+        type=int,
+        default=None,
+        help="Seed random (and numpy.random) before building the arguments and running the entry"
+    )
     parser.add_argument(
         "--stop_line",
         required=False,
         type=int
     )
+    parser.add_argument(
+        "--stop_at_entry",
+        action="store_true",
+        help="First pause on entering the entry function instead of at stop_line"
+    )
+    parser.add_argument(
+        "--entry_kind",
+        required=False,
+        choices=["function", "generator", "async"],
+        default=None,
+        help="How to drive the entry function (detected from the function when omitted)"
+    )
+    parser.add_argument(
+        "--events-on",
+        dest="events_on",
+        choices=["stdout", "stderr"],
+        default="stdout",
+        help="Stream to write JSON events to; the other one is left for diagnostics"
+    )
+    parser.add_argument(
+        "--step_scope",
+        choices=["project", "all"],
+        default="project",
+        help="Trace only frames of repo files (project) or library frames too (all)"
+    )
+    parser.add_argument(
+        "--with-context",
+        dest="with_context",
+        type=int,
+        default=0,
+        help="Attach this many source lines either side of the paused line to each event"
+    )
+    parser.add_argument(
+        "--backend",
+        choices=["settrace"],
+        default="settrace",
+        help="How execution is followed; settrace (bdb) is the only backend so far"
+    )
     parser.add_argument(
         "--get_signature",
         action="store_true",
         help="Get function signature instead of tracing"
     )
+    parser.add_argument(
+        "--with_decorators",
+        action="store_true",
+        help="Include the entry function's decorators in the signature"
+    )
+    parser.add_argument(
+        "--with_complexity",
+        action="store_true",
+        help="Include branch/loop counts of the entry function in the signature"
+    )
+    parser.add_argument(
+        "--get_callees",
+        action="store_true",
+        help="List the calls made by the entry function instead of tracing"
+    )
+    parser.add_argument(
+        "--get_callers",
+        action="store_true",
+        help="List repo functions that call the entry function instead of tracing"
+    )
+    parser.add_argument(
+        "--check_import",
+        action="store_true",
+        help="Only import the entry module and report whether it imports, instead of tracing"
+    )
+    parser.add_argument(
+        "--source",
+        action="store_true",
+        help="Print the entry function's source and the lines a breakpoint can stop on, instead of tracing"
+    )
+    parser.add_argument(
+        "--analyze",
+        action="store_true",
+        help="Print static hints about the entry function's cost and I/O, instead of tracing"
+    )
+    parser.add_argument(
+        "--cfg",
+        action="store_true",
+        help="Print each line of the entry function with the lines that can run next, instead of tracing"
+    )
+    parser.add_argument(
+        "--breakpoints",
+        required=False,
+        default="",
+        help="Comma-separated repo-relative file:line pairs to pause at, in any file"
+    )
+    parser.add_argument(
+        "--prewarm",
+        action="store_true",
+        help="Import the entry module, then wait for a start command on stdin before running"
+    )
+    parser.add_argument(
+        "--run_to_end",
+        action="store_true",
+        help="Run the entry function to completion, emit one end event and exit (1 if it raised)"
+    )
+    parser.add_argument(
+        "--profile",
+        action="store_true",
+        help="With --run_to_end, count how many times each line ran"
+    )
+    parser.add_argument(
+        "--sys-path",
+        dest="sys_path",
+        action="append",
+        default=[],
+        help="Repo-relative directory to put at the front of sys.path, e.g. src (repeatable)"
+    )
     args = parser.parse_args()
+
+    global EVENTS_STREAM
+    EVENTS_STREAM = sys.stdout if args.events_on == "stdout" else sys.stderr
+
+    # Listed order wins, so insert back to front
+    for rel_dir in reversed(args.sys_path):
+        sys.path.insert(0, os.path.join(args.repo_root, rel_dir))
     
     log(f"Command line arguments: repo_root={args.repo_root}, entry_full_id={args.entry_full_id}, stop_line={args.stop_line}, get_signature={args.get_signature}")
     
     # If --get_signature is set, return signature and exit
     if args.get_signature:
         log("Getting function signature")
-        result = get_function_signature(args.repo_root, args.entry_full_id)
+        result = get_function_signature(args.repo_root, args.entry_full_id, args.with_decorators, args.with_complexity)
         log(f"Signature result: {result}")
         print(json.dumps(result), flush=True)
         sys.exit(0)
+
+    if args.check_import:
+        log("Checking that the entry module imports")
+        print(json.dumps(check_importable(args.repo_root, args.entry_full_id)), flush=True)
+        sys.exit(0)
+
+    if args.get_callees or args.get_callers or args.cfg or args.analyze or args.source:
+        query = (get_callees if args.get_callees else get_callers if args.get_callers
+                 else get_cfg if args.cfg else get_source if args.source else analyze_function)
+        log(f"Running {query.__name__}")
+        try:
+            result = query(args.repo_root, args.entry_full_id)
+        except Exception as e:
+            log_exception(e, query.__name__)
+            result = {"error": str(e)}
+        print(json.dumps(result), flush=True)
+        sys.exit(0)
     
     # Otherwise, require stop_line
-    if args.stop_line is None:
+    if args.stop_line is None and not args.run_to_end:
         log("ERROR: --stop_line is required", "ERROR")
-        parser.error("--stop_line is required when not using --get_signature")
+        parser.error("--stop_line is required when not using --get_signature or --run_to_end")
 
     repo_root = args.repo_root
     entry_full_id = args.entry_full_id
@@ -325,18 +1396,10 @@ def main():
     log(f"  entry_full_id: {entry_full_id}")
     log(f"  stop_line: {stop_line}")
     log(f"  args_json: {args_json}")
+    log(f"  backend: {args.backend}")
+    log(f"  step_scope: {args.step_scope}")
 
-    with open("debugger_input.log", "a") as f:
-        f.write(f"{stop_line}\n")
-    args_list = []
-    kwargs_dict = {}
-    if args_json:
-        try:
-            parsed = json.loads(args_json)
-            args_list = parsed.get("args", [])
-            kwargs_dict = parsed.get("kwargs", {})
-        except Exception:
-            pass
+    args_list, kwargs_dict = parse_args_json(args_json)
 
     if "::" not in entry_full_id:
         log("ERROR: Invalid entry_full_id format (missing '::')", "ERROR")
@@ -354,7 +1417,8 @@ def main():
 
     try:
         log(f"Importing module from path: {rel_path}")
-        mod = import_module_from_path(repo_root, rel_path)
+        with quiet_stdout():
+            mod = import_module_from_path(repo_root, rel_path)
         log(f"Module imported successfully: {mod}")
     except Exception as e:
         error_msg = {
@@ -375,17 +1439,70 @@ def main():
     fn = getattr(mod, fn_name)
     log(f"Found function: {fn_name}, callable={callable(fn)}")
 
+    if args.prewarm:
+        # The slow part (importing the module) is done; wait for the actual request
+        log("Prewarmed, waiting for start command")
+        try:
+            start = json.loads(input())
+        except (EOFError, ValueError) as e:
+            log(f"No start command received ({e}), exiting")
+            sys.exit(0)
+        log(f"Received start command: {start}")
+        stop_line = start.get("stop_line")
+        args.stop_at_entry = bool(start.get("stop_at_entry"))
+        args_list, kwargs_dict = parse_args_json(start.get("args_json", ""))
+        args.setup_expr = start.get("setup_expr")
+        args.entry_kind = start.get("entry_kind") or args.entry_kind
+        args.seed = start.get("seed")
+        args.step_scope = start.get("step_scope") or args.step_scope
+
+    entry_kind = args.entry_kind or get_entry_kind(fn)
+    if entry_kind == "async_generator":
+        entry_kind = "async"
+    log(f"Entry kind: {entry_kind}")
+
+    if args.seed is not None:
+        seed_random(args.seed)
+
+    if args.setup_expr:
+        log(f"Evaluating setup_expr: {args.setup_expr}")
+        try:
+            with quiet_stdout():
+                args_list, kwargs_dict = build_args_from_setup(mod, args.setup_expr)
+        except Exception as e:
+            log_exception(e, "setup_expr")
+            send_event({
+                "event": "error",
+                "error": f"setup_expr failed: {e}",
+                "traceback": traceback.format_exc()
+            })
+            sys.exit(1)
+        log(f"setup_expr produced args={args_list}, kwargs={kwargs_dict}")
+
+    if args.run_to_end:
+        end_event = run_to_end(drive_entry(fn, entry_kind), args_list, kwargs_dict, repo_root, args.profile)
+        send_event(end_event)
+        sys.exit(1 if "exception" in end_event else 0)
+
     dbg = PersistentDebugger()
     dbg.target_file = abs_path  # Only this file counts for stop_line
     dbg.repo_root = repo_root
+    dbg.step_scope = args.step_scope
+    dbg.context_lines = max(args.with_context, 0)
+    dbg.breakpoints = parse_breakpoints(repo_root, args.breakpoints)
+    log(f"Breakpoints: {sorted(dbg.breakpoints)}")
     log(f"Created PersistentDebugger, target_file={abs_path}")
 
     log(f"Starting function execution with args={args_list}, kwargs={kwargs_dict}")
-    dbg.run_function_once(fn, args_list, kwargs_dict)
+    dbg.run_function_once(drive_entry(fn, entry_kind), args_list, kwargs_dict)
 
-    # Run until initial stop_line
-    log(f"Continuing until stop_line={stop_line}")
-    dbg.continue_until(stop_line)
+    if args.stop_at_entry:
+        log(f"Running to entry of {fn_name}")
+        dbg.run_to(fn_name)
+    else:
+        # Run until initial stop_line
+        log(f"Continuing until stop_line={stop_line}")
+        dbg.continue_until(stop_line)
     
     # Wait for event with timeout to detect if thread died
     log("Waiting for event (timeout=30.0s)")
@@ -448,6 +1565,7 @@ def main():
             "traceback": "The debugger may not have stopped at the expected line. The function may have completed before reaching the target line."
         }
         send_event(error_event)
+    end_step()
 
     # Interactive stepping
     log("Entering interactive stepping loop")
@@ -456,12 +1574,42 @@ def main():
             log("Waiting for user input (stdin)")
             user_input = input().strip()
             log(f"Received user input: '{user_input}'")
-            with open("debugger_input.log", "a") as f:
-                f.write(f"Received input: {user_input}\n")
 
             if not user_input or user_input == "0":
                 log("User input is empty or '0', breaking loop")
                 break
+            if user_input.startswith("{"):
+                # JSON commands inspect the paused frame without advancing
+                try:
+                    response = handle_command(dbg, json.loads(user_input))
+                except Exception as e:
+                    log_exception(e, "handle_command")
+                    response = {"event": "error", "error": str(e)}
+                send_event(response)
+                end_step()
+                continue
+            if user_input == "finish":
+                if dbg.finish():
+                    dbg.wait_for_event()
+                    send_event(dbg.last_event)
+                else:
+                    send_event({"event": "error", "error": "tracer is not paused in a frame"})
+                end_step()
+                continue
+            if user_input == "step_over":
+                if dbg.step_over():
+                    dbg.wait_for_event()
+                    send_event(dbg.last_event)
+                else:
+                    send_event({"event": "error", "error": "tracer is not paused in a frame"})
+                end_step()
+                continue
+            if user_input.startswith("run_to "):
+                dbg.run_to(user_input[len("run_to "):].strip())
+                dbg.wait_for_event()
+                send_event(dbg.last_event)
+                end_step()
+                continue
             line = int(user_input)
             log(f"Parsed line number: {line}")
             dbg.continue_until(line)
@@ -469,10 +1617,16 @@ def main():
             dbg.wait_for_event()
             log(f"Sending event: {dbg.last_event.get('event', 'unknown') if dbg.last_event else 'None'}")
             send_event(dbg.last_event)
+            end_step()
 
+        except EOFError:
+            log("stdin closed, exiting stepping loop")
+            break
         except Exception as e:
             log_exception(e, "interactive stepping loop")
-            # Don't print to stderr, just log it
+            # Still answer, or Rust waits forever for this step
+            send_event({"event": "error", "error": str(e)})
+            end_step()
     
     log("Tracer exiting")
     if _log_file: